@@ -0,0 +1,66 @@
+use serde::Serialize;
+
+use crate::node::Node;
+
+// 各区分の上限（最終更新からの経過時間）
+const DAY: u64 = 24 * 60 * 60; // [s]
+const WEEK: u64 = 7 * DAY; // [s]
+const MONTH: u64 = 30 * DAY; // [s]
+const YEAR: u64 = 365 * DAY; // [s]
+
+// 区分毎の集計
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AgeBucket {
+    pub size: u64,
+    pub count: u64,
+}
+
+// 最終更新日時による使用量の内訳
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AgeBreakdown {
+    pub day: AgeBucket,     // 1日以内
+    pub week: AgeBucket,    // 1週間以内
+    pub month: AgeBucket,   // 1ヶ月以内
+    pub year: AgeBucket,    // 1年以内
+    pub older: AgeBucket,   // 1年より前
+    pub unknown: AgeBucket, // メタデータが無い（collect_metadataが無効）
+}
+
+impl AgeBreakdown {
+    fn bucket_mut(&mut self, modified: Option<u64>, now: u64) -> &mut AgeBucket {
+        match modified {
+            // 未来の日時は1日以内として扱う
+            Some(modified) => match now.saturating_sub(modified) {
+                age if age <= DAY => &mut self.day,
+                age if age <= WEEK => &mut self.week,
+                age if age <= MONTH => &mut self.month,
+                age if age <= YEAR => &mut self.year,
+                _ => &mut self.older,
+            },
+            None => &mut self.unknown,
+        }
+    }
+}
+
+// ノード以下の使用量を最終更新日時で区分して集計（nowはUNIX時間 [s]）
+// ディレクトリ自体のサイズもディレクトリの更新日時で集計するため、合計はノードのサイズと一致する
+// "N small items"ノードは更新日時を持たないため、親ディレクトリのサイズとして集計する
+pub fn age_breakdown(node: &Node, now: u64) -> AgeBreakdown {
+    let mut breakdown = AgeBreakdown::default();
+    collect(node, now, &mut breakdown);
+    return breakdown;
+}
+
+fn collect(node: &Node, now: u64, breakdown: &mut AgeBreakdown) {
+    let children = node.children.iter().filter(|c| !c.is_collapsed());
+    let children_size: u64 = children.clone().map(|c| c.size).sum();
+    let modified = node.metadata.as_ref().and_then(|m| m.modified);
+
+    let bucket = breakdown.bucket_mut(modified, now);
+    bucket.size += node.size.saturating_sub(children_size);
+    bucket.count += 1;
+
+    for child in children {
+        collect(child, now, breakdown);
+    }
+}
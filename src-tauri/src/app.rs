@@ -0,0 +1,630 @@
+use regex::Regex;
+use serde::Serialize;
+use std::sync::Arc;
+use tauri::{Emitter, Manager};
+
+use crate::age_breakdown::age_breakdown;
+use crate::diff::diff_nodes;
+use crate::duplicates::{duplicate_candidates, find_duplicates};
+use crate::frontend_utils::{
+    check_full_disk_access_permission, get_protected_paths, move_to_trash, move_to_trash_batch,
+    open_file_manager, prepare_delete, remove_file_or_directory, remove_files_or_directories,
+    set_protected_paths,
+};
+use crate::init_walk::init_walk;
+use crate::init_walk::WalkParams;
+use crate::node::Node;
+use crate::node_to_json;
+use crate::progress::ProgressHandler;
+use crate::progress::ScanObserver;
+use crate::snapshot::load_snapshot;
+use crate::snapshot::ScanInfo;
+use crate::top_n::{top_n, TopNFilter};
+use crate::tree_watcher::TreeChange;
+use crate::type_breakdown::type_breakdown;
+use crate::utils::{normalize_path, unix_now};
+use crate::walk_error::WalkError;
+use crate::walk_manager::{ScanSession, WalkManager, DEFAULT_SESSION_ID};
+
+// WebViewへ途中経過を通知（通知にはセッションIDが含まれる）
+struct SessionObserver {
+    app: tauri::AppHandle,
+    session_id: u64,
+}
+
+// 走査が完了したサブツリーの通知内容
+#[derive(Serialize)]
+struct SubtreeNotification<'a> {
+    session_id: u64,
+    subtrees: &'a [Node],
+}
+
+impl ScanObserver for SessionObserver {
+    fn on_progress(&self, progress: &ProgressHandler) {
+        let encode_result: Result<String, _> = serde_json::to_string(progress);
+        match encode_result {
+            // 正常にエンコードできた場合はWebViewに送信
+            Ok(str) => self.app.emit("ProgressNotification", str).unwrap(),
+            // エンコードに失敗した場合
+            Err(err) => eprintln!("Progress encode error: {}", err),
+        }
+    }
+
+    fn on_subtrees(&self, subtrees: &[Node]) {
+        let notification = SubtreeNotification {
+            session_id: self.session_id,
+            subtrees,
+        };
+        let encode_result: Result<String, _> = serde_json::to_string(&notification);
+        match encode_result {
+            // 正常にエンコードできた場合はWebViewに送信
+            Ok(str) => self.app.emit("SubtreeNotification", str).unwrap(),
+            // エンコードに失敗した場合
+            Err(err) => eprintln!("Subtree encode error: {}", err),
+        }
+    }
+}
+
+// スキャン開始の通知内容（中断する際にscan_idを指定する）
+#[derive(Serialize)]
+struct ScanStartedNotification {
+    session_id: u64,
+    scan_id: u64,
+}
+
+// 監視によるツリーの変更の通知内容
+#[derive(Serialize)]
+struct TreeChangeNotification<'a> {
+    session_id: u64,
+    changes: &'a [TreeChange],
+}
+
+// 重複ファイル検出の途中経過をWebViewへ通知
+struct DuplicateObserver(tauri::AppHandle);
+
+impl ScanObserver for DuplicateObserver {
+    fn on_progress(&self, progress: &ProgressHandler) {
+        let encode_result: Result<String, _> = serde_json::to_string(progress);
+        match encode_result {
+            // 正常にエンコードできた場合はWebViewに送信
+            Ok(str) => self.0.emit("DuplicateProgressNotification", str).unwrap(),
+            // エンコードに失敗した場合
+            Err(err) => eprintln!("Progress encode error: {}", err),
+        }
+    }
+}
+
+// Walk Start（asyncで非同期とする）
+#[tauri::command(rename_all = "snake_case")]
+async fn walk_start(
+    str_params: &str,
+    session_id: Option<u64>,
+    state: tauri::State<'_, WalkManager>,
+    app: tauri::AppHandle,
+) -> Result<String, WalkError> {
+    let walk_params = decode_walk_params(str_params)?;
+
+    let session = get_session(&state, session_id)
+        .map_err(|message| WalkError::SessionNotFound { message })?;
+
+    // スキャンIDと中断用トークンを発行
+    let (scan_id, cancel_token) = session.begin_scan().ok_or_else(|| WalkError::SessionBusy {
+        message: format!(
+            "Scan already running in session {}",
+            session.get_session_id()
+        ),
+    })?;
+
+    // 走査を始める前にスキャンIDを通知
+    let started = ScanStartedNotification {
+        session_id: session.get_session_id(),
+        scan_id,
+    };
+    match serde_json::to_string(&started) {
+        Ok(str) => app.emit("ScanStartedNotification", str).unwrap(),
+        Err(err) => eprintln!("Scan started encode error: {}", err),
+    }
+
+    let observer = SessionObserver {
+        app,
+        session_id: session.get_session_id(),
+    };
+
+    // レスポンスに含める深さ
+    let response_depth = walk_params.response_depth;
+    // スキャン情報
+    let mut scan_info = ScanInfo::new(walk_params.clone());
+
+    // Walk
+    let walk_data = init_walk(
+        walk_params,
+        session.get_error_handler(),
+        session.get_progress_handler(),
+        &cancel_token,
+        Arc::new(observer),
+    );
+
+    session.end_scan(scan_id);
+
+    let walk_data = walk_data?;
+
+    // レスポンス用のノードを作成（深さが指定されている場合は指定の深さまでに制限）
+    // セッションには間引かないツリーを保持し、レスポンスのみを間引く
+    let response_node = walk_data
+        .as_ref()
+        .map(|node| node.clone_pruned(response_depth, &scan_info.params.prune_options(node.size)));
+
+    // エラーと除外したマウントポイントを記録
+    scan_info.cancelled = cancel_token.is_cancelled();
+    scan_info.set_result(
+        &session.get_error_handler().lock().unwrap(),
+        walk_data.as_ref(),
+    );
+
+    // スキャン情報をセット（強制終了した場合は空にする）
+    session.set_info(walk_data.as_ref().map(|_| scan_info));
+
+    // ノードをセット
+    session.set_node(walk_data);
+
+    // ノードをjsonに変換
+    return node_to_json(response_node).map_err(|message| WalkError::Encode { message });
+}
+
+// パラメータを検証（エラーが無い場合は空の配列を返す）
+#[tauri::command(rename_all = "snake_case")]
+fn validate_walk_params(str_params: &str) -> Result<String, WalkError> {
+    let walk_params = decode_walk_params(str_params)?;
+
+    let errors = crate::init_walk::validate_walk_params(&walk_params);
+
+    return serde_json::to_string(&errors).map_err(|err| WalkError::Encode {
+        message: err.to_string(),
+    });
+}
+
+fn decode_walk_params(str_params: &str) -> Result<WalkParams, WalkError> {
+    let decode_result: Result<WalkParams, _> = serde_json::from_str(str_params);
+
+    match decode_result {
+        // 正常にパラメータをデコードできた場合
+        Ok(walk_params) => Ok(walk_params),
+        // パラメータのデコードに失敗した場合
+        Err(err) => {
+            eprintln!("Parameter decode error: {}", err.to_string());
+            Err(WalkError::InvalidJson {
+                message: err.to_string(),
+            })
+        }
+    }
+}
+
+// ノードをリロード（asyncで非同期とする）
+#[tauri::command(rename_all = "snake_case")]
+async fn node_reload(
+    session_id: Option<u64>,
+    state: tauri::State<'_, WalkManager>,
+) -> Result<String, String> {
+    let session = get_session(&state, session_id)?;
+
+    // ノードを取得
+    let node = session.get_subtree(None, None);
+
+    // ノードをjsonに変換
+    return node_to_json(node);
+}
+
+// スキャン情報を取得（asyncで非同期とする）
+#[tauri::command(rename_all = "snake_case")]
+async fn get_scan_info(
+    session_id: Option<u64>,
+    state: tauri::State<'_, WalkManager>,
+) -> Result<String, String> {
+    let session = get_session(&state, session_id)?;
+
+    match session.get_info() {
+        Some(info) => serde_json::to_string(&info).map_err(|err| err.to_string()),
+        None => Ok("".to_string()),
+    }
+}
+
+// スナップショットを保存（asyncで非同期とする）
+#[tauri::command(rename_all = "snake_case")]
+async fn save_scan_snapshot(
+    path: String,
+    session_id: Option<u64>,
+    state: tauri::State<'_, WalkManager>,
+) -> Result<(), String> {
+    let session = get_session(&state, session_id)?;
+
+    return session.save_snapshot(&normalize_path(path));
+}
+
+// スナップショットを読み込み（asyncで非同期とする）
+#[tauri::command(rename_all = "snake_case")]
+async fn load_scan_snapshot(
+    path: String,
+    depth: Option<usize>,
+    session_id: Option<u64>,
+    state: tauri::State<'_, WalkManager>,
+) -> Result<String, String> {
+    let session = get_session(&state, session_id)?;
+
+    let snapshot = load_snapshot(&normalize_path(path)).map_err(|err| {
+        eprintln!("Snapshot load error: {}", err);
+        err
+    })?;
+
+    // レスポンス用のノードを作成（深さが指定されている場合は指定の深さまでに制限）
+    let prune = snapshot.info.params.prune_options(snapshot.node.size);
+    let response_node = snapshot.node.clone_pruned(depth, &prune);
+
+    // スキャン情報とノードをセット
+    session.set_info(Some(snapshot.info));
+    session.set_node(Some(snapshot.node));
+
+    // ノードをjsonに変換
+    return node_to_json(Some(response_node));
+}
+
+// スナップショットとの差分を取得（asyncで非同期とする）
+// new_pathを指定しない場合は現在のスキャン結果と比較する
+#[tauri::command(rename_all = "snake_case")]
+async fn diff_snapshots(
+    old_path: String,
+    new_path: Option<String>,
+    depth: Option<usize>,
+    session_id: Option<u64>,
+    state: tauri::State<'_, WalkManager>,
+) -> Result<String, String> {
+    let session = get_session(&state, session_id)?;
+
+    let old_snapshot = load_snapshot(&normalize_path(old_path))?;
+
+    let diff_result = match new_path {
+        Some(new_path) => {
+            let new_snapshot = load_snapshot(&normalize_path(new_path))?;
+            diff_nodes(&old_snapshot.node, &new_snapshot.node, depth)
+        }
+        None => session.with_node(|node| match node {
+            Some(node) => diff_nodes(&old_snapshot.node, node, depth),
+            None => Err("No scan result to compare".to_string()),
+        }),
+    };
+
+    match diff_result {
+        Ok(diff) => serde_json::to_string(&diff).map_err(|err| err.to_string()),
+        Err(err) => {
+            eprintln!("Diff error: {}", err);
+            Err(err)
+        }
+    }
+}
+
+// 重複ファイルを検出（asyncで非同期とする）
+// pathを指定した場合はそのパス以下のみを対象とする
+#[tauri::command(rename_all = "snake_case")]
+async fn find_duplicate_files(
+    path: Option<String>,
+    min_size: Option<u64>,
+    session_id: Option<u64>,
+    state: tauri::State<'_, WalkManager>,
+    app: tauri::AppHandle,
+) -> Result<String, String> {
+    let session = get_session(&state, session_id)?;
+
+    // 候補の抽出のみロック中に行い、ハッシュ計算はロックを解放してから行う
+    let candidates = session.with_node(|node| {
+        let target = match &path {
+            Some(path) => node.and_then(|node| node.find(&normalize_path(path))),
+            None => node,
+        };
+        target.map(|target| duplicate_candidates(target, min_size.unwrap_or(1)))
+    });
+
+    match candidates {
+        Some(candidates) => {
+            let progress = ProgressHandler {
+                session_id: session.get_session_id(),
+                ..ProgressHandler::default()
+            };
+            let report = find_duplicates(
+                candidates,
+                &Arc::new(progress),
+                Arc::new(DuplicateObserver(app)),
+            );
+            serde_json::to_string(&report).map_err(|err| err.to_string())
+        }
+        None => Err("No scan result to search".to_string()),
+    }
+}
+
+// 最終更新日時による使用量の内訳を取得（asyncで非同期とする）
+// pathを指定しない場合はスキャンのルートを対象とする
+#[tauri::command(rename_all = "snake_case")]
+async fn get_age_breakdown(
+    path: Option<String>,
+    session_id: Option<u64>,
+    state: tauri::State<'_, WalkManager>,
+) -> Result<String, String> {
+    let session = get_session(&state, session_id)?;
+
+    let breakdown = session.with_node(|node| {
+        let target = match &path {
+            Some(path) => node.and_then(|node| node.find(&normalize_path(path))),
+            None => node,
+        };
+        target.map(|target| age_breakdown(target, unix_now()))
+    });
+
+    match breakdown {
+        Some(breakdown) => serde_json::to_string(&breakdown).map_err(|err| err.to_string()),
+        None => Ok("".to_string()),
+    }
+}
+
+// 拡張子・分類別の使用量の内訳を取得（asyncで非同期とする）
+// pathを指定しない場合はスキャンのルートを対象とする
+#[tauri::command(rename_all = "snake_case")]
+async fn get_type_breakdown(
+    path: Option<String>,
+    session_id: Option<u64>,
+    state: tauri::State<'_, WalkManager>,
+) -> Result<String, String> {
+    let session = get_session(&state, session_id)?;
+
+    let breakdown = session.with_node(|node| {
+        let target = match &path {
+            Some(path) => node.and_then(|node| node.find(&normalize_path(path))),
+            None => node,
+        };
+        target.map(type_breakdown)
+    });
+
+    match breakdown {
+        Some(breakdown) => serde_json::to_string(&breakdown).map_err(|err| err.to_string()),
+        None => Ok("".to_string()),
+    }
+}
+
+// サイズの大きいファイル・ディレクトリを取得（asyncで非同期とする）
+// pathを指定しない場合はスキャンのルートを対象とする
+#[tauri::command(rename_all = "snake_case")]
+async fn get_top_n(
+    n: usize,
+    path: Option<String>,
+    extensions: Option<Vec<String>>,
+    regex: Option<String>,
+    session_id: Option<u64>,
+    state: tauri::State<'_, WalkManager>,
+) -> Result<String, String> {
+    let session = get_session(&state, session_id)?;
+
+    let regex = match regex {
+        Some(regex) => Some(Regex::new(&regex).map_err(|err| err.to_string())?),
+        None => None,
+    };
+
+    let filter = TopNFilter {
+        extensions: extensions
+            .unwrap_or_default()
+            .iter()
+            .map(|ext| ext.trim_start_matches('.').to_lowercase())
+            .collect(),
+        regex,
+    };
+
+    let report = session.with_node(|node| {
+        let target = match &path {
+            Some(path) => node.and_then(|node| node.find(&normalize_path(path))),
+            None => node,
+        };
+        target.map(|target| top_n(target, n, &filter))
+    });
+
+    match report {
+        Some(report) => serde_json::to_string(&report).map_err(|err| err.to_string()),
+        None => Ok("".to_string()),
+    }
+}
+
+// 指定したパス以下のノードを取得（asyncで非同期とする）
+#[tauri::command(rename_all = "snake_case")]
+async fn get_subtree(
+    path: String,
+    depth: usize,
+    session_id: Option<u64>,
+    state: tauri::State<'_, WalkManager>,
+) -> Result<String, String> {
+    let session = get_session(&state, session_id)?;
+
+    // ノードを取得
+    let node = session.get_subtree(Some(&normalize_path(path)), Some(depth));
+
+    // ノードをjsonに変換
+    return node_to_json(node);
+}
+
+// 指定したパス直下の子ノードを取得（asyncで非同期とする）
+#[tauri::command(rename_all = "snake_case")]
+async fn get_children(
+    path: String,
+    offset: usize,
+    limit: usize,
+    session_id: Option<u64>,
+    state: tauri::State<'_, WalkManager>,
+) -> Result<String, String> {
+    let session = get_session(&state, session_id)?;
+
+    match session.get_children(&normalize_path(path), offset, limit) {
+        // ノードが存在する場合
+        Some(children) => {
+            let encode_result: Result<String, _> = serde_json::to_string(&children);
+
+            match encode_result {
+                // 正常にノードデータをエンコードできた場合
+                Ok(str_children) => Ok(str_children),
+                // ノードデータのエンコードに失敗した場合
+                Err(err) => {
+                    eprintln!("Node encode error: {}", err.to_string());
+                    Err(err.to_string())
+                }
+            }
+        }
+        // ノードが存在しない場合
+        None => Ok("".to_string()),
+    }
+}
+
+// スキャンのルートの監視を開始（変更はTreeChangeNotificationで通知する）
+#[tauri::command(rename_all = "snake_case")]
+fn start_watching(
+    session_id: Option<u64>,
+    state: tauri::State<'_, WalkManager>,
+    app: tauri::AppHandle,
+) -> Result<(), String> {
+    let session = get_session(&state, session_id)?;
+
+    if session.is_running() {
+        return Err("Cannot watch while scanning".to_string());
+    }
+
+    let session_id = session.get_session_id();
+    return session.start_watch(move |changes| {
+        let notification = TreeChangeNotification {
+            session_id,
+            changes,
+        };
+        let encode_result: Result<String, _> = serde_json::to_string(&notification);
+        match encode_result {
+            // 正常にエンコードできた場合はWebViewに送信
+            Ok(str) => app.emit("TreeChangeNotification", str).unwrap(),
+            // エンコードに失敗した場合
+            Err(err) => eprintln!("Tree change encode error: {}", err),
+        }
+    });
+}
+
+// スキャンのルートの監視を終了
+#[tauri::command(rename_all = "snake_case")]
+fn stop_watching(
+    session_id: Option<u64>,
+    state: tauri::State<'_, WalkManager>,
+) -> Result<(), String> {
+    let session = get_session(&state, session_id)?;
+    session.stop_watch();
+    return Ok(());
+}
+
+// 強制終了（ScanStartedNotificationで通知したscan_idに一致するスキャンのみを中断する）
+#[tauri::command(rename_all = "snake_case")]
+fn abort(session_id: Option<u64>, scan_id: u64, state: tauri::State<'_, WalkManager>) {
+    match state.get_session(session_id) {
+        Some(session) if session.cancel_scan(Some(scan_id)) => {}
+        _ => eprintln!("No scan to abort: {:?} {:?}", session_id, scan_id),
+    }
+}
+
+// 新しいセッションを作成（作成したセッションIDを返す）
+#[tauri::command(rename_all = "snake_case")]
+fn create_session(state: tauri::State<'_, WalkManager>) -> u64 {
+    return state.create_session();
+}
+
+// セッションを削除
+#[tauri::command(rename_all = "snake_case")]
+fn close_session(session_id: u64, state: tauri::State<'_, WalkManager>) -> Result<(), String> {
+    return state.close_session(session_id);
+}
+
+// セッションの一覧を取得
+#[tauri::command(rename_all = "snake_case")]
+fn list_sessions(state: tauri::State<'_, WalkManager>) -> Result<String, String> {
+    return serde_json::to_string(&state.list_sessions()).map_err(|err| err.to_string());
+}
+
+// セッションを取得
+pub(crate) fn get_session(
+    state: &WalkManager,
+    session_id: Option<u64>,
+) -> Result<Arc<ScanSession>, String> {
+    return state.get_session(session_id).ok_or_else(|| {
+        format!(
+            "No such session: {}",
+            session_id.unwrap_or(DEFAULT_SESSION_ID)
+        )
+    });
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    tauri::Builder::default()
+        .setup(move |app| {
+            let walk_manager = WalkManager::new();
+            app.manage(walk_manager);
+
+            // MacOSのみメニューを生成
+            #[cfg(target_os = "macos")]
+            {
+                use tauri::menu::{AboutMetadata, Menu, SubmenuBuilder};
+
+                let menu = Menu::new(app)?;
+
+                let mut submenu = SubmenuBuilder::new(app, "File")
+                    .about_with_text("About", Some(AboutMetadata::default()))
+                    .separator()
+                    .quit_with_text("Quit")
+                    .build()?;
+
+                menu.append(&submenu)?;
+
+                submenu = SubmenuBuilder::new(app, "Window")
+                    .minimize()
+                    .separator()
+                    .close_window()
+                    .build()?;
+
+                menu.append(&submenu)?;
+
+                app.set_menu(menu)?;
+            }
+
+            Ok(())
+        })
+        .plugin(tauri_plugin_shell::init())
+        .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_clipboard_manager::init())
+        .plugin(tauri_plugin_opener::init())
+        .invoke_handler(tauri::generate_handler![
+            walk_start,
+            validate_walk_params,
+            node_reload,
+            get_subtree,
+            get_children,
+            get_scan_info,
+            save_scan_snapshot,
+            load_scan_snapshot,
+            diff_snapshots,
+            find_duplicate_files,
+            get_age_breakdown,
+            get_type_breakdown,
+            get_top_n,
+            start_watching,
+            stop_watching,
+            abort,
+            create_session,
+            close_session,
+            list_sessions,
+            prepare_delete,
+            remove_file_or_directory,
+            remove_files_or_directories,
+            open_file_manager,
+            check_full_disk_access_permission,
+            move_to_trash,
+            move_to_trash_batch,
+            get_protected_paths,
+            set_protected_paths
+        ])
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
+}
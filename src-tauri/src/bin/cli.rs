@@ -0,0 +1,527 @@
+// コマンドライン版（WebViewを使用せずにスキャンを実行する）
+use std::path::Path;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri_app_lib::{
+    age_breakdown, diff_nodes, duplicate_candidates, find_duplicates, init_walk, load_snapshot,
+    save_snapshot, top_n, type_breakdown, unix_now, AgeBreakdown, CancelToken, DiffNode,
+    DiffStatus, DuplicateReport, ErrorHandler, Node, NodeStatus, ProgressHandler, ScanInfo,
+    ScanObserver, TopNFilter, TopNReport, TypeBreakdown, WalkParams,
+};
+
+const USAGE: &str = "\
+Usage: dir_walker-cli scan <path> [options]
+       dir_walker-cli show <snapshot> [options]
+       dir_walker-cli diff <old snapshot> <new snapshot> [options]
+
+Commands:
+  scan <path>             Scan a directory
+  show <snapshot>         Print a snapshot saved with --save
+  diff <old> <new>        Print what grew or shrank between two snapshots
+
+Options:
+  --ignore <dir>          Ignore a directory (relative to <path>), may be repeated
+  --regex <regex>         Only count files matching the regex, may be repeated
+  --invert-regex <regex>  Exclude files matching the regex, may be repeated
+  --exclude <glob>        Exclude entries matching a gitignore style glob, may be repeated
+  --include <glob>        Only count files matching the glob, may be repeated
+  --use-ignore-files      Honour .gitignore and .ignore files found during the scan
+  --apparent-size         Use apparent size instead of disk usage
+  -x, --one-file-system   Do not descend into other file systems
+  -L, --follow-symlinks   Follow symbolic links to directories
+  --metadata              Collect times, owner and mode of each entry (with --json)
+  --depth <n>             Depth of entries to print (default: 1)
+  --max-depth <n>         Aggregate entries deeper than n into their parent
+  --min-size <bytes>      Collapse entries smaller than the size into one entry
+  --min-percent <p>       Collapse entries smaller than p% of the total into one entry
+  --human-readable        Print sizes in human readable format
+  --json                  Print the whole tree as JSON
+  --save <file>           Save the scan result as a snapshot file
+  --cache <file>          Reuse unchanged directories from the cache file and update it
+  --duplicates            Print groups of duplicate files instead of sizes
+  --age                   Print usage by last modification time (requires --metadata)
+  --types                 Print usage by file category and extension
+  --top <n>               Print the n largest files and directories
+  -h, --help              Print this help";
+
+// 表示単位
+const UNITS: [&str; 6] = ["B", "K", "M", "G", "T", "P"];
+
+// 途中経過は出力せず、エラーのみ標準エラー出力に出力する
+struct CliObserver;
+
+impl ScanObserver for CliObserver {
+    fn on_progress(&self, _progress: &ProgressHandler) {}
+}
+
+enum CliCommand {
+    Scan,
+    Show,
+    Diff,
+}
+
+// 出力する内容
+enum Report {
+    Sizes,
+    Duplicates,
+    Age,
+    Types,
+    Top(usize),
+}
+
+struct CliOptions {
+    command: CliCommand,
+    path: String,
+    new_path: String,
+    walk_params: WalkParams,
+    save: Option<String>,
+    report: Report,
+    json: bool,
+    depth: usize,
+    human_readable: bool,
+}
+
+// 引数を解析
+fn parse_args(args: &[String]) -> Result<CliOptions, String> {
+    let mut args = args.iter();
+
+    let command = match args.next().map(|s| s.as_str()) {
+        Some("scan") => CliCommand::Scan,
+        Some("show") => CliCommand::Show,
+        Some("diff") => CliCommand::Diff,
+        Some(other) => return Err(format!("Unknown command: {}", other)),
+        None => return Err("No command specified".to_string()),
+    };
+
+    let mut options = CliOptions {
+        command,
+        path: String::new(),
+        new_path: String::new(),
+        walk_params: WalkParams::default(),
+        save: None,
+        report: Report::Sizes,
+        json: false,
+        depth: 1,
+        human_readable: false,
+    };
+    let mut regex_filter = vec![];
+    let mut regex_invert_filter = vec![];
+    let mut ignore_directories = vec![];
+    let mut exclude_patterns = vec![];
+    let mut include_patterns = vec![];
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--ignore" => ignore_directories.push(next_value(&mut args, arg)?),
+            "--regex" => regex_filter.push(next_value(&mut args, arg)?),
+            "--invert-regex" => regex_invert_filter.push(next_value(&mut args, arg)?),
+            "--exclude" => exclude_patterns.push(next_value(&mut args, arg)?),
+            "--include" => include_patterns.push(next_value(&mut args, arg)?),
+            "--use-ignore-files" => options.walk_params.use_ignore_files = true,
+            "--apparent-size" => options.walk_params.use_apparent_size = true,
+            "--metadata" => options.walk_params.collect_metadata = true,
+            "-x" | "--one-file-system" => options.walk_params.same_file_system = true,
+            "-L" | "--follow-symlinks" => options.walk_params.follow_symlinks = true,
+            "--json" => options.json = true,
+            "--save" => options.save = Some(next_value(&mut args, arg)?),
+            "--cache" => options.walk_params.cache_file = Some(next_value(&mut args, arg)?),
+            "--duplicates" => options.report = Report::Duplicates,
+            "--age" => options.report = Report::Age,
+            "--types" => options.report = Report::Types,
+            "--top" => {
+                let value = next_value(&mut args, arg)?;
+                let n = value
+                    .parse()
+                    .map_err(|_| format!("Invalid number: {}", value))?;
+                options.report = Report::Top(n);
+            }
+            "--human-readable" => options.human_readable = true,
+            "--depth" => {
+                let value = next_value(&mut args, arg)?;
+                options.depth = value
+                    .parse()
+                    .map_err(|_| format!("Invalid depth: {}", value))?;
+            }
+            "--max-depth" => {
+                let value = next_value(&mut args, arg)?;
+                options.walk_params.max_depth = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("Invalid depth: {}", value))?,
+                );
+            }
+            "--min-size" => {
+                let value = next_value(&mut args, arg)?;
+                options.walk_params.min_size = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("Invalid size: {}", value))?,
+                );
+            }
+            "--min-percent" => {
+                let value = next_value(&mut args, arg)?;
+                options.walk_params.min_percentage = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("Invalid percentage: {}", value))?,
+                );
+            }
+            _ if arg.starts_with('-') => return Err(format!("Unknown option: {}", arg)),
+            _ if options.path.is_empty() => options.path = arg.clone(),
+            _ if matches!(options.command, CliCommand::Diff) && options.new_path.is_empty() => {
+                options.new_path = arg.clone();
+            }
+            _ => return Err(format!("Unexpected argument: {}", arg)),
+        }
+    }
+
+    if options.path.is_empty() {
+        return Err("No path specified".to_string());
+    }
+
+    if matches!(options.command, CliCommand::Diff) && options.new_path.is_empty() {
+        return Err("No new snapshot specified".to_string());
+    }
+
+    options.walk_params.target_directory = options.path.clone();
+    options.walk_params.regex_filter = Some(regex_filter);
+    options.walk_params.regex_invert_filter = Some(regex_invert_filter);
+    options.walk_params.ignore_directories = Some(ignore_directories);
+    options.walk_params.exclude_patterns = Some(exclude_patterns);
+    options.walk_params.include_patterns = Some(include_patterns);
+
+    return Ok(options);
+}
+
+// オプションの値を取得
+fn next_value<'a>(
+    args: &mut impl Iterator<Item = &'a String>,
+    option: &str,
+) -> Result<String, String> {
+    args.next()
+        .cloned()
+        .ok_or_else(|| format!("Missing value for {}", option))
+}
+
+// サイズを文字列に変換
+fn format_size(size: u64, human_readable: bool) -> String {
+    if !human_readable {
+        return size.to_string();
+    }
+
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{}{}", size, UNITS[0])
+    } else {
+        format!("{:.1}{}", value, UNITS[unit])
+    }
+}
+
+// du形式で出力（子ノードを先に出力する）
+fn print_du(node: &Node, depth: usize, human_readable: bool) {
+    if depth > 0 {
+        let mut children: Vec<&Node> = node.children.iter().collect();
+        children.sort_by_key(|child| std::cmp::Reverse(child.size));
+        for child in children {
+            print_du(child, depth - 1, human_readable);
+        }
+    }
+
+    // サイズが下限値の場合は状態を付記
+    let status = match node.status {
+        NodeStatus::Ok => "",
+        NodeStatus::PermissionDenied => " (permission denied)",
+        NodeStatus::Partial => " (partial)",
+        NodeStatus::Vanished => " (vanished)",
+    };
+
+    println!(
+        "{}\t{}{}",
+        format_size(node.size, human_readable),
+        node.name.display(),
+        status
+    );
+}
+
+// スキャンを実行（--saveが指定されている場合はスナップショットを保存）
+fn scan(options: &CliOptions) -> Option<Node> {
+    let errors = Arc::new(Mutex::new(ErrorHandler::default()));
+    let progress = Arc::new(ProgressHandler::default());
+
+    let mut scan_info = ScanInfo::new(options.walk_params.clone());
+
+    let node = match init_walk(
+        options.walk_params.clone(),
+        &errors,
+        &progress,
+        &CancelToken::default(),
+        Arc::new(CliObserver),
+    ) {
+        Ok(node) => node,
+        Err(err) => {
+            eprintln!("{}", err);
+            return None;
+        }
+    };
+
+    scan_info.set_result(&errors.lock().unwrap(), node.as_ref());
+
+    if let Some(unaccounted_size) = scan_info.unaccounted_size {
+        eprintln!("Unaccounted: {} bytes", unaccounted_size);
+    }
+    if let Some(counted_size) = scan_info.errors_counted_size {
+        eprintln!(
+            "Counted under errored paths: {} bytes (lower bound)",
+            counted_size
+        );
+    }
+
+    if let (Some(node), Some(save)) = (&node, &options.save) {
+        if let Err(err) = save_snapshot(Path::new(save), &scan_info, node) {
+            eprintln!("Snapshot save error: {}", err);
+        }
+    }
+
+    return node;
+}
+
+// 差分を出力（子ノードを先に出力する）
+fn print_diff(node: &DiffNode, depth: usize, human_readable: bool) {
+    if depth > 0 {
+        for child in node.children.iter() {
+            if child.status != DiffStatus::Unchanged {
+                print_diff(child, depth - 1, human_readable);
+            }
+        }
+    }
+
+    let sign = if node.delta < 0 { "-" } else { "+" };
+    let marker = match node.status {
+        DiffStatus::Added => " (added)",
+        DiffStatus::Removed => " (removed)",
+        _ => "",
+    };
+    println!(
+        "{}{}\t{}{}",
+        sign,
+        format_size(node.delta.unsigned_abs(), human_readable),
+        node.name.display(),
+        marker
+    );
+}
+
+// jsonで出力
+fn print_json<T: Serialize>(value: &T) -> ExitCode {
+    match serde_json::to_string(value) {
+        Ok(json) => {
+            println!("{}", json);
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("Encode error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// 最終更新日時による内訳を出力
+fn print_age_breakdown(breakdown: &AgeBreakdown, human_readable: bool) {
+    for (label, bucket) in [
+        ("1 day", &breakdown.day),
+        ("1 week", &breakdown.week),
+        ("1 month", &breakdown.month),
+        ("1 year", &breakdown.year),
+        ("older", &breakdown.older),
+        ("unknown", &breakdown.unknown),
+    ] {
+        println!(
+            "{}\t{}\t{} entries",
+            label,
+            format_size(bucket.size, human_readable),
+            bucket.count
+        );
+    }
+}
+
+// 分類・拡張子別の内訳を出力
+fn print_type_breakdown(breakdown: &TypeBreakdown, human_readable: bool) {
+    for group in breakdown
+        .by_category
+        .iter()
+        .chain(breakdown.by_extension.iter())
+    {
+        let name = if group.name.is_empty() {
+            "(none)"
+        } else {
+            &group.name
+        };
+        println!(
+            "{}\t{}\t{} files",
+            name,
+            format_size(group.size, human_readable),
+            group.count
+        );
+    }
+}
+
+// サイズの大きいファイル・ディレクトリを出力
+fn print_top_n(report: &TopNReport, human_readable: bool) {
+    for (title, entries) in [
+        ("Largest files", &report.files),
+        ("Largest directories", &report.directories),
+        ("Largest directories (own files)", &report.directories_own),
+    ] {
+        println!("{}:", title);
+        for entry in entries.iter() {
+            println!(
+                "{}\t{}",
+                format_size(entry.size, human_readable),
+                entry.name.display()
+            );
+        }
+    }
+}
+
+// 重複ファイルを出力
+fn print_duplicates(report: &DuplicateReport, human_readable: bool) {
+    for group in report.groups.iter() {
+        println!(
+            "{} x {} ({} reclaimable)",
+            format_size(group.size, human_readable),
+            group.paths.len(),
+            format_size(group.reclaimable, human_readable)
+        );
+        for path in group.paths.iter() {
+            println!("\t{}", path.display());
+        }
+    }
+    println!(
+        "Total reclaimable: {}",
+        format_size(report.total_reclaimable, human_readable)
+    );
+}
+
+// 2つのスナップショットの差分を出力
+fn diff(options: &CliOptions) -> ExitCode {
+    let snapshots = load_snapshot(Path::new(&options.path))
+        .and_then(|old| Ok((old, load_snapshot(Path::new(&options.new_path))?)));
+
+    let diff_result =
+        snapshots.and_then(|(old, new)| diff_nodes(&old.node, &new.node, Some(options.depth)));
+
+    match diff_result {
+        Ok(diff) if options.json => match serde_json::to_string(&diff) {
+            Ok(json) => println!("{}", json),
+            Err(err) => {
+                eprintln!("Diff encode error: {}", err);
+                return ExitCode::FAILURE;
+            }
+        },
+        Ok(diff) => print_diff(&diff, options.depth, options.human_readable),
+        Err(err) => {
+            eprintln!("Diff error: {}", err);
+            return ExitCode::FAILURE;
+        }
+    }
+
+    return ExitCode::SUCCESS;
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let node = match options.command {
+        CliCommand::Diff => return diff(&options),
+        CliCommand::Scan => scan(&options),
+        CliCommand::Show => match load_snapshot(Path::new(&options.path)) {
+            Ok(snapshot) => Some(snapshot.node),
+            Err(err) => {
+                eprintln!("Snapshot load error: {}", err);
+                return ExitCode::FAILURE;
+            }
+        },
+    };
+
+    let node = match node {
+        Some(node) => node,
+        None => {
+            eprintln!("Scan failed");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let human_readable = options.human_readable;
+
+    // 集計は間引かないツリーに対して行い、出力するツリーのみを間引く
+    let prune = options.walk_params.prune_options(node.size);
+
+    match options.report {
+        Report::Sizes if options.json => print_json(&node.clone_pruned(None, &prune)),
+        Report::Sizes => {
+            print_du(
+                &node.clone_pruned(None, &prune),
+                options.depth,
+                human_readable,
+            );
+            ExitCode::SUCCESS
+        }
+        Report::Duplicates => {
+            let report = find_duplicates(
+                duplicate_candidates(&node, 1),
+                &Arc::new(ProgressHandler::default()),
+                Arc::new(CliObserver),
+            );
+            if options.json {
+                return print_json(&report);
+            }
+            print_duplicates(&report, human_readable);
+            ExitCode::SUCCESS
+        }
+        Report::Age => {
+            let breakdown = age_breakdown(&node, unix_now());
+            if options.json {
+                return print_json(&breakdown);
+            }
+            print_age_breakdown(&breakdown, human_readable);
+            ExitCode::SUCCESS
+        }
+        Report::Types => {
+            let breakdown = type_breakdown(&node);
+            if options.json {
+                return print_json(&breakdown);
+            }
+            print_type_breakdown(&breakdown, human_readable);
+            ExitCode::SUCCESS
+        }
+        Report::Top(n) => {
+            let report = top_n(&node, n, &TopNFilter::default());
+            if options.json {
+                return print_json(&report);
+            }
+            print_top_n(&report, human_readable);
+            ExitCode::SUCCESS
+        }
+    }
+}
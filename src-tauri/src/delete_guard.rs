@@ -0,0 +1,403 @@
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::hash::BuildHasher;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::AtomicU64;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::platform::is_mount_point;
+use crate::progress::ORDERING;
+
+// 確認用トークンの有効期間
+pub const TOKEN_LIFETIME: u64 = 300; // [s]
+
+// 削除を許可しないシステムのディレクトリ（これらを含むディレクトリも削除できない）
+#[cfg(target_os = "windows")]
+const SYSTEM_PATHS: &[&str] = &[
+    "C:\\",
+    "C:\\Windows",
+    "C:\\Program Files",
+    "C:\\Program Files (x86)",
+    "C:\\ProgramData",
+    "C:\\Users",
+];
+#[cfg(target_os = "macos")]
+const SYSTEM_PATHS: &[&str] = &[
+    "/",
+    "/Applications",
+    "/Library",
+    "/System",
+    "/Users",
+    "/Volumes",
+    "/bin",
+    "/cores",
+    "/dev",
+    "/etc",
+    "/opt",
+    "/private",
+    "/sbin",
+    "/tmp",
+    "/usr",
+    "/var",
+];
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+const SYSTEM_PATHS: &[&str] = &[
+    "/", "/bin", "/boot", "/dev", "/etc", "/home", "/lib", "/lib32", "/lib64", "/media", "/mnt",
+    "/opt", "/proc", "/root", "/run", "/sbin", "/srv", "/sys", "/tmp", "/usr", "/var",
+];
+
+// 確認済みの削除対象
+struct PendingDeletion {
+    session_id: u64,
+    paths: HashSet<PathBuf>,
+    expires: Instant,
+}
+
+// 削除操作の保護（削除を許可しないパスの一覧と確認用トークンを管理する）
+pub struct DeleteGuard {
+    protected_paths: Mutex<Vec<PathBuf>>, // 削除を許可しないパス
+    pending: Mutex<HashMap<String, PendingDeletion>>, // 発行済みのトークン
+    last_token_id: AtomicU64,
+}
+
+impl DeleteGuard {
+    // 初期化
+    pub fn new() -> Self {
+        Self {
+            protected_paths: Mutex::new(default_protected_paths()),
+            pending: Mutex::new(HashMap::new()),
+            last_token_id: AtomicU64::new(0),
+        }
+    }
+
+    // 削除を許可しないパスを取得
+    pub fn get_protected_paths(&self) -> Vec<PathBuf> {
+        return self.protected_paths.lock().unwrap().clone();
+    }
+
+    // 削除を許可しないパスを設定（Noneの場合は既定値に戻す）
+    pub fn set_protected_paths(&self, paths: Option<Vec<PathBuf>>) {
+        *self.protected_paths.lock().unwrap() = paths.unwrap_or_else(default_protected_paths);
+    }
+
+    // 削除してよいパスか確認（許可しない場合は理由を返す）
+    // シンボリックリンクはリンク自体を対象とし、親ディレクトリのリンクは解決した実際の場所で判定する
+    pub fn check(&self, path: &Path, root: &Path) -> Result<(), String> {
+        if path.components().any(|c| c == Component::ParentDir) {
+            return Err(format!("Relative path is not allowed: {}", path.display()));
+        }
+
+        let md =
+            fs::symlink_metadata(path).map_err(|err| format!("{}: {}", err, path.display()))?;
+
+        let (Some(parent), Some(file_name)) = (path.parent(), path.file_name()) else {
+            return Err(format!("Protected path: {}", path.display()));
+        };
+        let real_path = fs::canonicalize(parent)
+            .map_err(|err| format!("{}: {}", err, parent.display()))?
+            .join(file_name);
+        let real_root = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+
+        if !path.starts_with(root) || !real_path.starts_with(&real_root) {
+            return Err(format!("Outside of the scan root: {}", path.display()));
+        }
+
+        // 保護されたパス自体またはそれを含むディレクトリは削除できない
+        for protected in self.protected_paths.lock().unwrap().iter() {
+            let real_protected = fs::canonicalize(protected).unwrap_or_else(|_| protected.clone());
+            if protected.starts_with(path) || real_protected.starts_with(&real_path) {
+                return Err(format!("Protected path: {}", protected.display()));
+            }
+        }
+
+        if md.is_dir() && is_mount_point(path) {
+            return Err(format!("Mount point: {}", path.display()));
+        }
+
+        return Ok(());
+    }
+
+    // 確認済みのパスに対してトークンを発行
+    pub fn issue_token(&self, session_id: u64, paths: Vec<PathBuf>) -> String {
+        let token_id = self.last_token_id.fetch_add(1, ORDERING) + 1;
+        let token = new_token(token_id);
+
+        let mut locked_pending = self.pending.lock().unwrap();
+        locked_pending.retain(|_, pending| pending.expires > Instant::now());
+        locked_pending.insert(
+            token.clone(),
+            PendingDeletion {
+                session_id,
+                paths: paths.into_iter().collect(),
+                expires: Instant::now() + Duration::from_secs(TOKEN_LIFETIME),
+            },
+        );
+
+        return token;
+    }
+
+    // トークンを使用（一度だけ使用でき、発行時に確認したパスのみ削除できる）
+    pub fn redeem_token(
+        &self,
+        token: &str,
+        session_id: u64,
+        paths: &[PathBuf],
+    ) -> Result<(), String> {
+        let mut locked_pending = self.pending.lock().unwrap();
+        let pending = locked_pending
+            .remove(token)
+            .filter(|pending| pending.expires > Instant::now())
+            .ok_or_else(|| "Invalid or expired confirmation token".to_string())?;
+
+        if pending.session_id != session_id {
+            return Err("Confirmation token was issued for another session".to_string());
+        }
+
+        if let Some(path) = paths.iter().find(|path| !pending.paths.contains(*path)) {
+            return Err(format!("Not confirmed for deletion: {}", path.display()));
+        }
+
+        return Ok(());
+    }
+}
+
+impl Default for DeleteGuard {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+// 削除を許可しないパスの既定値（システムのディレクトリとホームディレクトリ）
+fn default_protected_paths() -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = SYSTEM_PATHS.iter().map(PathBuf::from).collect();
+
+    let home = if cfg!(target_os = "windows") {
+        std::env::var_os("USERPROFILE")
+    } else {
+        std::env::var_os("HOME")
+    };
+    if let Some(home) = home {
+        paths.push(PathBuf::from(home));
+    }
+
+    return paths;
+}
+
+// 推測できないトークンを生成
+fn new_token(token_id: u64) -> String {
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+
+    return format!(
+        "{:016x}{:016x}",
+        RandomState::new().hash_one((token_id, nanos)),
+        RandomState::new().hash_one((nanos, token_id))
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    // root/a/b/keep（保護）, root/a/file, root/c
+    struct Fixture {
+        _dir: TempDir,
+        root: PathBuf,
+        guard: DeleteGuard,
+    }
+
+    fn setup() -> Fixture {
+        let dir = TempDir::new().unwrap();
+        // 一時ディレクトリ自体がシンボリックリンクを含む場合（macOSの/tmpなど）があるため正規化
+        let root = fs::canonicalize(dir.path()).unwrap();
+
+        fs::create_dir_all(root.join("a/b/keep")).unwrap();
+        fs::create_dir_all(root.join("c")).unwrap();
+        fs::write(root.join("a/file"), b"data").unwrap();
+
+        let guard = DeleteGuard::new();
+        guard.set_protected_paths(Some(vec![root.join("a/b/keep")]));
+
+        return Fixture {
+            _dir: dir,
+            root,
+            guard,
+        };
+    }
+
+    fn assert_denied(result: Result<(), String>, reason: &str) {
+        match result {
+            Ok(()) => panic!("expected denial: {}", reason),
+            Err(err) => assert!(err.starts_with(reason), "{}", err),
+        }
+    }
+
+    #[test]
+    fn check_allows_entries_inside_root() {
+        let f = setup();
+
+        assert!(f.guard.check(&f.root.join("a/file"), &f.root).is_ok());
+        assert!(f.guard.check(&f.root.join("c"), &f.root).is_ok());
+    }
+
+    #[test]
+    fn check_rejects_protected_paths_and_their_ancestors() {
+        let f = setup();
+
+        assert_denied(
+            f.guard.check(&f.root.join("a/b/keep"), &f.root),
+            "Protected path",
+        );
+        assert_denied(
+            f.guard.check(&f.root.join("a/b"), &f.root),
+            "Protected path",
+        );
+        assert_denied(f.guard.check(&f.root.join("a"), &f.root), "Protected path");
+    }
+
+    #[test]
+    fn check_rejects_paths_outside_root() {
+        let f = setup();
+        let outside = TempDir::new().unwrap();
+        let outside_file = outside.path().join("file");
+        fs::write(&outside_file, b"data").unwrap();
+
+        assert_denied(
+            f.guard.check(&outside_file, &f.root),
+            "Outside of the scan root",
+        );
+        assert_denied(
+            f.guard.check(&f.root.join("c/../a/file"), &f.root),
+            "Relative path is not allowed",
+        );
+        assert!(f.guard.check(&f.root.join("missing"), &f.root).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn check_resolves_symlinked_parents() {
+        use std::os::unix::fs::symlink;
+
+        let f = setup();
+        let outside = TempDir::new().unwrap();
+        fs::write(outside.path().join("file"), b"data").unwrap();
+
+        symlink(outside.path(), f.root.join("out")).unwrap();
+        symlink(f.root.join("a"), f.root.join("alias")).unwrap();
+
+        // リンク自体はルート内のため削除できる
+        assert!(f.guard.check(&f.root.join("out"), &f.root).is_ok());
+        // リンクを経由したルート外のファイル
+        assert_denied(
+            f.guard.check(&f.root.join("out/file"), &f.root),
+            "Outside of the scan root",
+        );
+        // リンクを経由した保護されたパスを含むディレクトリ
+        assert_denied(
+            f.guard.check(&f.root.join("alias/b"), &f.root),
+            "Protected path",
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn check_rejects_mount_points() {
+        let guard = DeleteGuard::new();
+        guard.set_protected_paths(Some(vec![]));
+
+        // procfsは常に別のファイルシステムとしてマウントされている
+        assert_denied(
+            guard.check(Path::new("/proc"), Path::new("/")),
+            "Mount point",
+        );
+    }
+
+    #[test]
+    fn token_is_single_use() {
+        let guard = DeleteGuard::new();
+        let paths = vec![PathBuf::from("/r/a"), PathBuf::from("/r/b")];
+
+        let token = guard.issue_token(1, paths.clone());
+
+        assert!(guard.redeem_token(&token, 1, &paths).is_ok());
+        assert_denied(
+            guard.redeem_token(&token, 1, &paths),
+            "Invalid or expired confirmation token",
+        );
+    }
+
+    #[test]
+    fn token_allows_subset_of_confirmed_paths() {
+        let guard = DeleteGuard::new();
+
+        let token = guard.issue_token(1, vec![PathBuf::from("/r/a"), PathBuf::from("/r/b")]);
+
+        assert!(guard
+            .redeem_token(&token, 1, &[PathBuf::from("/r/b")])
+            .is_ok());
+    }
+
+    #[test]
+    fn token_rejects_unconfirmed_paths() {
+        let guard = DeleteGuard::new();
+
+        let token = guard.issue_token(1, vec![PathBuf::from("/r/a")]);
+
+        assert_denied(
+            guard.redeem_token(&token, 1, &[PathBuf::from("/r/a"), PathBuf::from("/r/c")]),
+            "Not confirmed for deletion: /r/c",
+        );
+    }
+
+    #[test]
+    fn token_rejects_other_sessions() {
+        let guard = DeleteGuard::new();
+        let paths = vec![PathBuf::from("/r/a")];
+
+        let token = guard.issue_token(1, paths.clone());
+
+        assert_denied(
+            guard.redeem_token(&token, 2, &paths),
+            "Confirmation token was issued for another session",
+        );
+    }
+
+    #[test]
+    fn token_expires() {
+        let guard = DeleteGuard::new();
+        let paths = vec![PathBuf::from("/r/a")];
+
+        let token = guard.issue_token(1, paths.clone());
+        guard
+            .pending
+            .lock()
+            .unwrap()
+            .get_mut(&token)
+            .unwrap()
+            .expires = Instant::now();
+
+        assert_denied(
+            guard.redeem_token(&token, 1, &paths),
+            "Invalid or expired confirmation token",
+        );
+    }
+
+    #[test]
+    fn tokens_are_unique() {
+        let guard = DeleteGuard::new();
+
+        let first = guard.issue_token(1, vec![]);
+        let second = guard.issue_token(1, vec![]);
+
+        assert_ne!(first, second);
+        assert_denied(
+            guard.redeem_token("unknown", 1, &[]),
+            "Invalid or expired confirmation token",
+        );
+    }
+}
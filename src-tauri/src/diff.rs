@@ -0,0 +1,115 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::node::Node;
+
+// 差分の状態
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffStatus {
+    Added,     // 新しいスキャンにのみ存在
+    Removed,   // 古いスキャンにのみ存在
+    Changed,   // サイズが変化
+    Unchanged, // サイズが同じ
+}
+
+// 2つのスキャン結果の差分ノード
+#[derive(Debug, Clone, Serialize)]
+pub struct DiffNode {
+    pub name: PathBuf,
+    pub old_size: u64,
+    pub new_size: u64,
+    pub delta: i64,
+    pub status: DiffStatus,
+    pub children: Vec<DiffNode>,
+    pub depth: usize,
+}
+
+// 2つのスキャン結果の差分を作成（子ノードは変化量の絶対値の降順）
+// max_depthを指定した場合はそれより深いノードを含めない（サイズは集計済み）
+pub fn diff_nodes(old: &Node, new: &Node, max_depth: Option<usize>) -> Result<DiffNode, String> {
+    if old.name != new.name {
+        return Err(format!(
+            "Scan roots do not match: {} and {}",
+            old.name.display(),
+            new.name.display()
+        ));
+    }
+
+    return Ok(diff(Some(old), Some(new), max_depth));
+}
+
+fn diff(old: Option<&Node>, new: Option<&Node>, max_depth: Option<usize>) -> DiffNode {
+    // どちらか一方は必ず存在する
+    let base = new.or(old).unwrap();
+
+    let old_size = old.map_or(0, |node| node.size);
+    let new_size = new.map_or(0, |node| node.size);
+
+    let status = match (old, new) {
+        (None, Some(_)) => DiffStatus::Added,
+        (Some(_), None) => DiffStatus::Removed,
+        _ if old_size != new_size => DiffStatus::Changed,
+        _ => DiffStatus::Unchanged,
+    };
+
+    let children = if max_depth.is_some_and(|max_depth| base.depth >= max_depth) {
+        vec![]
+    } else {
+        diff_children(old, new, max_depth)
+    };
+
+    DiffNode {
+        name: base.name.clone(),
+        old_size,
+        new_size,
+        delta: new_size as i64 - old_size as i64,
+        status,
+        children,
+        depth: base.depth,
+    }
+}
+
+// 子ノードをパスで突き合わせて差分を作成
+fn diff_children(
+    old: Option<&Node>,
+    new: Option<&Node>,
+    max_depth: Option<usize>,
+) -> Vec<DiffNode> {
+    let old_children: HashMap<&Path, &Node> = old
+        .map(|node| {
+            node.children
+                .iter()
+                .map(|child| (child.name.as_path(), child))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut children: Vec<DiffNode> = vec![];
+
+    // 新しいスキャンの子ノード（追加・変化）
+    if let Some(new) = new {
+        for child in new.children.iter() {
+            let old_child = old_children.get(child.name.as_path()).copied();
+            children.push(diff(old_child, Some(child), max_depth));
+        }
+    }
+
+    // 古いスキャンにのみ存在する子ノード（削除）
+    let new_names: HashSet<&Path> = new
+        .map(|node| node.children.iter().map(|c| c.name.as_path()).collect())
+        .unwrap_or_default();
+    if let Some(old) = old {
+        for child in old.children.iter() {
+            if !new_names.contains(&child.name.as_path()) {
+                children.push(diff(Some(child), None, max_depth));
+            }
+        }
+    }
+
+    children.sort_by_key(|child| std::cmp::Reverse(child.delta.unsigned_abs()));
+
+    return children;
+}
@@ -1,21 +1,28 @@
 use std::fs;
+use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 use std::sync::Mutex;
 
-use crate::node::Node;
+use crate::node::{Node, NodeStatus, PruneOptions};
+use crate::progress::CancelToken;
 use crate::progress::ErrorHandler;
 use crate::progress::ProgressHandler;
+use crate::progress::ScanError;
 use crate::progress::ORDERING;
+use crate::scan_cache::{CachedDirectory, CachedEntry, ScanCache};
 use crate::utils::is_filtered_out_due_to_invert_regex;
 use crate::utils::is_filtered_out_due_to_regex;
+use rayon::iter::IntoParallelRefIterator;
 use rayon::iter::ParallelBridge;
 use rayon::prelude::ParallelIterator;
 use regex::Regex;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
+use crate::ignore_rules::{GlobRules, IgnoreStack};
 use crate::node::build_node;
+use crate::platform::{get_device, get_directory_id, get_group_name, get_user_name};
 use std::fs::DirEntry;
 
 pub struct WalkData<'a> {
@@ -23,8 +30,27 @@ pub struct WalkData<'a> {
     pub filter_regex: &'a [Regex],
     pub invert_filter_regex: &'a [Regex],
     pub use_apparent_size: bool,
+    pub collect_metadata: bool,
+    pub glob_rules: GlobRules,
+    pub use_ignore_files: bool,
+    pub root_device: Option<u64>, // 指定した場合は別のファイルシステムに降りない
+    pub follow_symlinks: bool,
+    pub visited_directories: Mutex<VisitedDirectories>, // 走査済みディレクトリ（循環検出用）
+    pub stream_depth: Option<usize>,
+    pub stream_prune: PruneOptions, // 途中結果として通知するサブツリーの間引き方
+    pub response_depth: Option<usize>,
     pub progress_data: Arc<ProgressHandler>,
     pub errors: Arc<Mutex<ErrorHandler>>,
+    pub cancel_token: CancelToken,
+    pub previous_cache: Option<ScanCache>, // 前回のスキャンのキャッシュ
+    pub updated_cache: Option<Mutex<ScanCache>>, // 今回のスキャンで作成するキャッシュ
+}
+
+// 走査済みディレクトリ
+#[derive(Default)]
+pub struct VisitedDirectories {
+    owners: HashMap<(u64, u64), (PathBuf, bool)>, // (inode, device) -> 走査したパスとシンボリックリンク経由かどうか
+    superseded: HashSet<PathBuf>, // 後から実際のパスで到達したため結果から取り除くシンボリックリンク経由のパス
 }
 
 /* -------------------------------------------------------------------------- */
@@ -34,10 +60,26 @@ pub fn walk_it(dir: PathBuf, walk_data: &WalkData) -> Option<Node> {
 
     let prog_data = &walk_data.progress_data;
     prog_data.clear_state();
+    walk_data.errors.lock().unwrap().clear_state();
+    *walk_data.visited_directories.lock().unwrap() = VisitedDirectories::default();
+
+    // ルートを走査済みとして登録
+    if walk_data.follow_symlinks {
+        if let Ok(md) = fs::metadata(&dir) {
+            mark_visited(&dir, &md, false, walk_data);
+        }
+    }
 
-    match walk(dir, walk_data, 0) {
+    match walk(dir, walk_data, 0, false, &IgnoreStack::root()) {
         Some(node) => {
-            return clean_inodes(node, &mut inodes, walk_data.use_apparent_size);
+            let node = drop_superseded(node, walk_data);
+            let mut node = clean_inodes(node, &mut inodes, walk_data.use_apparent_size);
+
+            if let Some(ref mut node) = node {
+                finish_node(node, walk_data);
+            }
+
+            return node;
         }
         None => {
             return None;
@@ -45,8 +87,203 @@ pub fn walk_it(dir: PathBuf, walk_data: &WalkData) -> Option<Node> {
     };
 }
 
+// スキャン済みのツリーの一部を再走査（ファイル監視による更新時に使用）
+// pathはrootからの深さがdepthのパスで、除外対象や存在しない場合はNoneとなる
+// ハードリンクの重複除去はサブツリー内でのみ行う
+pub fn walk_subtree(
+    path: PathBuf,
+    walk_data: &WalkData,
+    depth: usize,
+    root: &Path,
+) -> Option<Node> {
+    let md = fs::symlink_metadata(&path).ok()?;
+
+    if walk_data.ignore_directories.contains(&path)
+        || walk_data.glob_rules.is_excluded(&path, md.is_dir())
+    {
+        return None;
+    }
+
+    if md.is_file()
+        && (is_filtered_out_due_to_regex(walk_data.filter_regex, &path)
+            || is_filtered_out_due_to_invert_regex(walk_data.invert_filter_regex, &path))
+    {
+        return None;
+    }
+
+    if md.is_dir() && is_other_device(path.clone(), Some(md.clone()), walk_data) {
+        return None;
+    }
+
+    // rootから親ディレクトリまでの.gitignore/.ignoreを読み込む
+    let mut ancestors: Vec<&Path> = if walk_data.use_ignore_files {
+        path.ancestors()
+            .skip(1)
+            .take_while(|ancestor| ancestor.starts_with(root))
+            .collect()
+    } else {
+        vec![]
+    };
+    ancestors.reverse();
+
+    let node = with_ancestor_ignores(&ancestors, &IgnoreStack::root(), &mut |ignores| {
+        if walk_data.use_ignore_files && ignores.is_ignored(&path, md.is_dir()) {
+            return None;
+        }
+
+        // 辿らないシンボリックリンクはファイルとして扱う
+        if md.is_symlink() && !is_followable_symlink(&path, walk_data) {
+            return build_node(
+                path.clone(),
+                vec![],
+                walk_data.filter_regex,
+                walk_data.invert_filter_regex,
+                walk_data.use_apparent_size,
+                true,
+                walk_data.collect_metadata,
+                depth,
+            );
+        }
+
+        return walk(path.clone(), walk_data, depth, md.is_symlink(), ignores);
+    })?;
+
+    let node = drop_superseded(node, walk_data);
+    let mut node = clean_inodes(node, &mut HashSet::new(), walk_data.use_apparent_size);
+    if let Some(ref mut node) = node {
+        finish_node(node, walk_data);
+    }
+    return node;
+}
+
+// root以下のパスが走査の対象外かどうか（パス自体または祖先が除外される場合はtrue）
+// 除外パターン、無視ファイル、別のファイルシステム、辿らないシンボリックリンクを考慮する
+pub fn is_excluded_path(path: &Path, walk_data: &WalkData, root: &Path) -> bool {
+    if !path.starts_with(root) {
+        return true;
+    }
+
+    let root_ignores = IgnoreStack::root();
+    let dir_ignores;
+    let ignores = if walk_data.use_ignore_files {
+        dir_ignores = root_ignores.push(root);
+        &dir_ignores
+    } else {
+        &root_ignores
+    };
+    return is_excluded_below(root, path, walk_data, ignores);
+}
+
+// dir直下のpathを含むエントリーから順に判定
+fn is_excluded_below(dir: &Path, path: &Path, walk_data: &WalkData, ignores: &IgnoreStack) -> bool {
+    let Some(name) = path
+        .strip_prefix(dir)
+        .ok()
+        .and_then(|relative| relative.components().next())
+    else {
+        return false;
+    };
+    let entry = dir.join(name);
+
+    // 削除された場合はメタデータを取得できないためファイルとして判定する
+    let md = fs::symlink_metadata(&entry).ok();
+    let is_dir = md.as_ref().is_some_and(|md| md.is_dir());
+    let is_symlink = md.as_ref().is_some_and(|md| md.is_symlink());
+
+    if walk_data.ignore_directories.contains(&entry)
+        || walk_data.glob_rules.is_excluded(&entry, is_dir)
+        || (walk_data.use_ignore_files && ignores.is_ignored(&entry, is_dir))
+    {
+        return true;
+    }
+
+    if entry == path {
+        return md.as_ref().is_some_and(|md| md.is_file())
+            && (is_filtered_out_due_to_regex(walk_data.filter_regex, &entry)
+                || is_filtered_out_due_to_invert_regex(walk_data.invert_filter_regex, &entry));
+    }
+
+    // 祖先が辿らないシンボリックリンクや別のファイルシステムの場合
+    if (is_symlink && !walk_data.follow_symlinks)
+        || (walk_data.root_device.is_some() && is_other_device(entry.clone(), md, walk_data))
+    {
+        return true;
+    }
+
+    let entry_ignores;
+    let ignores = if walk_data.use_ignore_files {
+        entry_ignores = ignores.push(&entry);
+        &entry_ignores
+    } else {
+        ignores
+    };
+    return is_excluded_below(&entry, path, walk_data, ignores);
+}
+
+// 祖先ディレクトリの無視ファイルを順に読み込んでからfを実行
+fn with_ancestor_ignores(
+    ancestors: &[&Path],
+    ignores: &IgnoreStack,
+    f: &mut dyn FnMut(&IgnoreStack) -> Option<Node>,
+) -> Option<Node> {
+    match ancestors.split_first() {
+        Some((dir, rest)) => {
+            let dir_ignores = ignores.push(dir);
+            return with_ancestor_ignores(rest, &dir_ignores, f);
+        }
+        None => {
+            return f(ignores);
+        }
+    }
+}
+
 /* -------------------------------------------------------------------------- */
 
+// 実際のパスでも到達したディレクトリをシンボリックリンク経由の結果から取り除く
+// リンク自体は辿らないリンクと同様にファイルとして残す（集計前のため祖先のサイズの更新は不要）
+fn drop_superseded(x: Node, walk_data: &WalkData) -> Node {
+    let superseded = std::mem::take(&mut walk_data.visited_directories.lock().unwrap().superseded);
+    if superseded.is_empty() {
+        return x;
+    }
+    return drop_paths(x, &superseded, walk_data);
+}
+
+fn drop_paths(x: Node, paths: &HashSet<PathBuf>, walk_data: &WalkData) -> Node {
+    let via_symlink = x.via_symlink;
+
+    let children = x
+        .children
+        .into_iter()
+        .filter_map(|child| {
+            if !paths.contains(&child.name) {
+                return Some(drop_paths(child, paths, walk_data));
+            }
+
+            let is_symlink = fs::symlink_metadata(&child.name).is_ok_and(|md| md.is_symlink());
+            if !is_symlink {
+                return None;
+            }
+            return build_node(
+                child.name,
+                vec![],
+                walk_data.filter_regex,
+                walk_data.invert_filter_regex,
+                walk_data.use_apparent_size,
+                true,
+                walk_data.collect_metadata,
+                child.depth,
+            )
+            .map(|node| Node {
+                via_symlink,
+                ..node
+            });
+        })
+        .collect();
+
+    Node { children, ..x }
+}
+
 // Remove files which have the same inode, we don't want to double count them.
 fn clean_inodes(
     x: Node,
@@ -68,17 +305,49 @@ fn clean_inodes(
         .collect();
 
     Some(Node {
-        name: x.name,
         size: x.size + new_children.iter().map(|c| c.size).sum::<u64>(),
         children: new_children,
         inode_device: None, // メモリ削減
-        depth: x.depth,
+        ..x
     })
 }
 
+// 所有者名の解決（結果のツリーは間引かず、返却する複製のみをPruneOptionsで間引く）
+fn finish_node(node: &mut Node, walk_data: &WalkData) {
+    if walk_data.collect_metadata {
+        resolve_owner_names(node, &mut HashMap::new(), &mut HashMap::new());
+    }
+}
+
+// uid/gidからユーザー名/グループ名を解決（同じIDは一度だけ問い合わせる）
+fn resolve_owner_names(
+    x: &mut Node,
+    users: &mut HashMap<u32, Option<String>>,
+    groups: &mut HashMap<u32, Option<String>>,
+) {
+    if let Some(ref mut metadata) = x.metadata {
+        if let Some(uid) = metadata.uid {
+            metadata.owner = users
+                .entry(uid)
+                .or_insert_with(|| get_user_name(uid))
+                .clone();
+        }
+        if let Some(gid) = metadata.gid {
+            metadata.group = groups
+                .entry(gid)
+                .or_insert_with(|| get_group_name(gid))
+                .clone();
+        }
+    }
+
+    for child in x.children.iter_mut() {
+        resolve_owner_names(child, users, groups);
+    }
+}
+
 /* -------------------------------------------------------------------------- */
 
-fn ignore_file(entry: &DirEntry, walk_data: &WalkData) -> bool {
+fn ignore_file(entry: &DirEntry, walk_data: &WalkData, ignores: &IgnoreStack) -> bool {
     // Keeping `walk_data.filter_regex.is_empty()` is important for performance reasons, it stops unnecessary work
     if !walk_data.filter_regex.is_empty()
         && entry.path().is_file()
@@ -94,93 +363,412 @@ fn ignore_file(entry: &DirEntry, walk_data: &WalkData) -> bool {
         return true;
     }
 
-    return walk_data.ignore_directories.contains(&entry.path());
+    if walk_data.ignore_directories.contains(&entry.path()) {
+        return true;
+    }
+
+    // globパターンと.gitignore/.ignoreに一致するものは降りる前に除外
+    let is_dir = entry.file_type().is_ok_and(|data| data.is_dir());
+    if walk_data.glob_rules.is_excluded(&entry.path(), is_dir) {
+        return true;
+    }
+    return walk_data.use_ignore_files && ignores.is_ignored(&entry.path(), is_dir);
+}
+
+// ルートと異なるファイルシステムのディレクトリ（マウントポイント）かどうか
+fn is_other_file_system(entry: &DirEntry, walk_data: &WalkData) -> bool {
+    if walk_data.root_device.is_none() {
+        return false;
+    }
+    return is_other_device(entry.path(), entry.metadata().ok(), walk_data);
+}
+
+fn is_other_device(path: PathBuf, md: Option<fs::Metadata>, walk_data: &WalkData) -> bool {
+    if let Some(root_device) = walk_data.root_device {
+        if let Some(device) = md.and_then(|md| get_device(&path, &md)) {
+            if device != root_device {
+                let mut editable_error = walk_data.errors.lock().unwrap();
+                editable_error.skipped_mount_points.push(path);
+                return true;
+            }
+        }
+    }
+    return false;
+}
+
+// ディレクトリを走査済みとして登録（走査する場合はtrue、IDを取得できない場合はNone）
+// 同じディレクトリにシンボリックリンク経由と実際のパスの両方で到達した場合は実際のパスを走査し、
+// 先に走査を始めたシンボリックリンク経由のパスは走査後に結果から取り除く
+fn mark_visited(
+    path: &Path,
+    md: &fs::Metadata,
+    via_symlink: bool,
+    walk_data: &WalkData,
+) -> Option<bool> {
+    let id = get_directory_id(path, md)?;
+
+    let mut visited = walk_data.visited_directories.lock().unwrap();
+    if let Some((owner, owner_via_symlink)) = visited.owners.get(&id) {
+        if via_symlink || !owner_via_symlink {
+            return Some(false);
+        }
+        let owner = owner.clone();
+        visited.superseded.insert(owner);
+    }
+    visited.owners.insert(id, (path.to_path_buf(), via_symlink));
+    return Some(true);
+}
+
+// 辿るべきディレクトリへのシンボリックリンクかどうか（走査済みのディレクトリを指す場合は循環とみなして辿らない）
+fn is_followable_symlink(path: &Path, walk_data: &WalkData) -> bool {
+    if !walk_data.follow_symlinks {
+        return false;
+    }
+    // リンク先のメタデータ
+    match fs::metadata(path) {
+        Ok(md) if md.is_dir() => {
+            if is_other_device(path.to_path_buf(), Some(md.clone()), walk_data) {
+                return false;
+            }
+            // IDを取得できない場合は循環を検出できないため辿らない
+            return mark_visited(path, &md, true, walk_data).unwrap_or(false);
+        }
+        _ => {
+            return false;
+        }
+    }
+}
+
+// キャッシュしたディレクトリの内容を再利用（ファイルはそのまま使用し、サブディレクトリは再帰的に走査する）
+fn walk_cached(
+    dir: &Path,
+    cached: &CachedDirectory,
+    walk_data: &WalkData,
+    depth: usize,
+    via_symlink: bool,
+    ignores: &IgnoreStack,
+) -> Vec<Node> {
+    let prog_data = &walk_data.progress_data;
+
+    let files = cached.files.iter().map(|file| {
+        prog_data.num_files.fetch_add(1, ORDERING);
+        prog_data.total_file_size.fetch_add(file.size, ORDERING);
+
+        Node {
+            name: dir.join(&file.name),
+            depth: depth + 1,
+            via_symlink,
+            ..file.clone()
+        }
+    });
+
+    let subdirectories: Vec<Node> = cached
+        .subdirectories
+        .par_iter()
+        .filter_map(|entry| {
+            // 中断された場合は残りのエントリーを読み飛ばす
+            if walk_data.cancel_token.is_cancelled() {
+                return None;
+            }
+
+            let path = dir.join(&entry.name);
+
+            if entry.is_symlink {
+                if is_followable_symlink(&path, walk_data) {
+                    return walk(path, walk_data, depth + 1, true, ignores);
+                }
+                return None;
+            }
+
+            if walk_data.root_device.is_some()
+                && is_other_device(path.clone(), fs::symlink_metadata(&path).ok(), walk_data)
+            {
+                return None;
+            }
+            // シンボリックリンクを辿る場合は循環検出のために登録（走査済みの場合は走査しない）
+            if walk_data.follow_symlinks {
+                if let Ok(md) = fs::symlink_metadata(&path) {
+                    if !mark_visited(&path, &md, via_symlink, walk_data).unwrap_or(true) {
+                        return None;
+                    }
+                }
+            }
+            return walk(path, walk_data, depth + 1, via_symlink, ignores);
+        })
+        .collect();
+
+    return files.chain(subdirectories).collect();
+}
+
+// キャッシュするサブディレクトリを追加
+fn push_subdirectory(
+    subdirectories: &Mutex<Vec<CachedEntry>>,
+    entry: &DirEntry,
+    is_symlink: bool,
+    walk_data: &WalkData,
+) {
+    if walk_data.updated_cache.is_some() {
+        subdirectories.lock().unwrap().push(CachedEntry {
+            name: PathBuf::from(entry.file_name()),
+            is_symlink,
+        });
+    }
+}
+
+// 子ノードのうちキャッシュするファイル（サブディレクトリ以外）
+fn cached_files(children: &[Node], subdirectories: &[CachedEntry]) -> Vec<Node> {
+    let names: HashSet<&Path> = subdirectories
+        .iter()
+        .map(|entry| entry.name.as_path())
+        .collect();
+
+    children
+        .iter()
+        .filter_map(|child| {
+            let name = Path::new(child.name.file_name()?);
+            if names.contains(name) {
+                return None;
+            }
+            Some(Node {
+                name: name.to_path_buf(),
+                ..child.clone()
+            })
+        })
+        .collect()
 }
 
-fn walk(dir: PathBuf, walk_data: &WalkData, depth: usize) -> Option<Node> {
+// 走査したディレクトリの内容をキャッシュに追加
+fn update_cache(
+    walk_data: &WalkData,
+    md: &fs::Metadata,
+    files: Vec<Node>,
+    subdirectories: Vec<CachedEntry>,
+) {
+    if let Some(ref cache) = walk_data.updated_cache {
+        cache.lock().unwrap().insert(md, files, subdirectories);
+    }
+}
+
+fn walk(
+    dir: PathBuf,
+    walk_data: &WalkData,
+    depth: usize,
+    via_symlink: bool,
+    ignores: &IgnoreStack,
+) -> Option<Node> {
     let prog_data = &walk_data.progress_data;
     let errors = &walk_data.errors;
 
-    // abortフラグでNoneをリターン
-    if errors.lock().unwrap().abort {
+    // 中断された場合はNoneをリターン
+    if walk_data.cancel_token.is_cancelled() {
         return None;
     }
 
-    let children = if dir.is_dir() {
+    // ディレクトリの読み込み状態
+    let mut status = NodeStatus::Ok;
+
+    // シンボリックリンクを辿った場合はリンク先で判定
+    let is_dir = dir.is_dir();
+
+    let children = if is_dir {
         // dirがディレクトリの場合
-        let read_dir = fs::read_dir(&dir);
-        match read_dir {
-            Ok(entries) => {
-                entries
-                    .into_iter()
-                    .par_bridge()
-                    .filter_map(|entry| {
-                        if let Ok(ref entry) = entry {
-                            // uncommenting the below line gives simpler code but
-                            // rayon doesn't parallelize as well giving a 3X performance drop
-                            // hence we unravel the recursion a bit
-
-                            // return walk(entry.path(), walk_data, depth)
-
-                            if !ignore_file(entry, walk_data) {
-                                if let Ok(data) = entry.file_type() {
-                                    if data.is_dir() {
-                                        return walk(entry.path(), walk_data, depth + 1);
-                                    }
 
-                                    let node = build_node(
-                                        entry.path(),
-                                        vec![],
-                                        walk_data.filter_regex,
-                                        walk_data.invert_filter_regex,
-                                        walk_data.use_apparent_size,
-                                        data.is_symlink(),
-                                        depth + 1,
-                                    );
-
-                                    prog_data.num_files.fetch_add(1, ORDERING);
-                                    if let Some(ref file) = node {
-                                        prog_data.total_file_size.fetch_add(file.size, ORDERING);
-                                    }
+        // ディレクトリ内の.gitignore/.ignoreを読み込む
+        let dir_ignores;
+        let ignores = if walk_data.use_ignore_files {
+            dir_ignores = ignores.push(&dir);
+            &dir_ignores
+        } else {
+            ignores
+        };
+
+        // ディレクトリのメタデータ（キャッシュを作成する場合のみ）
+        let dir_md = match walk_data.updated_cache {
+            Some(_) => fs::metadata(&dir).ok(),
+            None => None,
+        };
+
+        // 前回のスキャンから更新されていないディレクトリはキャッシュを再利用
+        let cached = match (&walk_data.previous_cache, &dir_md) {
+            (Some(cache), Some(md)) => cache.lookup(md),
+            _ => None,
+        };
 
-                                    return node;
+        if let Some(cached) = cached {
+            let children = walk_cached(&dir, cached, walk_data, depth, via_symlink, ignores);
+
+            if walk_data.cancel_token.is_cancelled()
+                || children.iter().any(|child| child.is_incomplete())
+            {
+                status = NodeStatus::Partial;
+            } else if let Some(ref md) = dir_md {
+                update_cache(
+                    walk_data,
+                    md,
+                    cached.files.clone(),
+                    cached.subdirectories.clone(),
+                );
+            }
+            children
+        } else {
+            let read_dir = fs::read_dir(&dir);
+            match read_dir {
+                Ok(entries) => {
+                    // 一部のエントリーを読み込めなかった場合
+                    let partial = AtomicBool::new(false);
+
+                    // キャッシュするサブディレクトリ
+                    let subdirectories = Mutex::new(vec![]);
+
+                    let children: Vec<Node> = entries
+                        .into_iter()
+                        .par_bridge()
+                        .filter_map(|entry| {
+                            // 中断された場合は残りのエントリーを読み飛ばす
+                            if walk_data.cancel_token.is_cancelled() {
+                                return None;
+                            }
+
+                            if let Ok(ref entry) = entry {
+                                // uncommenting the below line gives simpler code but
+                                // rayon doesn't parallelize as well giving a 3X performance drop
+                                // hence we unravel the recursion a bit
+
+                                // return walk(entry.path(), walk_data, depth)
+
+                                if !ignore_file(entry, walk_data, ignores) {
+                                    if let Ok(data) = entry.file_type() {
+                                        if data.is_dir() {
+                                            if is_other_file_system(entry, walk_data) {
+                                                return None;
+                                            }
+                                            // シンボリックリンクを辿る場合は循環検出のために登録（走査済みの場合は走査しない）
+                                            if walk_data.follow_symlinks {
+                                                if let Ok(md) = entry.metadata() {
+                                                    if !mark_visited(
+                                                        &entry.path(),
+                                                        &md,
+                                                        via_symlink,
+                                                        walk_data,
+                                                    )
+                                                    .unwrap_or(true)
+                                                    {
+                                                        return None;
+                                                    }
+                                                }
+                                            }
+                                            push_subdirectory(
+                                                &subdirectories,
+                                                entry,
+                                                false,
+                                                walk_data,
+                                            );
+                                            return walk(
+                                                entry.path(),
+                                                walk_data,
+                                                depth + 1,
+                                                via_symlink,
+                                                ignores,
+                                            );
+                                        }
+
+                                        // ディレクトリへのシンボリックリンクを辿る
+                                        if data.is_symlink()
+                                            && is_followable_symlink(&entry.path(), walk_data)
+                                        {
+                                            push_subdirectory(
+                                                &subdirectories,
+                                                entry,
+                                                true,
+                                                walk_data,
+                                            );
+                                            return walk(
+                                                entry.path(),
+                                                walk_data,
+                                                depth + 1,
+                                                true,
+                                                ignores,
+                                            );
+                                        }
+
+                                        let node = build_node(
+                                            entry.path(),
+                                            vec![],
+                                            walk_data.filter_regex,
+                                            walk_data.invert_filter_regex,
+                                            walk_data.use_apparent_size,
+                                            data.is_symlink(),
+                                            walk_data.collect_metadata,
+                                            depth + 1,
+                                        )
+                                        .map(|node| Node {
+                                            via_symlink,
+                                            ..node
+                                        });
+
+                                        prog_data.num_files.fetch_add(1, ORDERING);
+                                        if let Some(ref file) = node {
+                                            prog_data
+                                                .total_file_size
+                                                .fetch_add(file.size, ORDERING);
+                                        }
+
+                                        return node;
+                                    }
                                 }
+                            } else if let Err(ref failed) = entry {
+                                partial.store(true, ORDERING);
+                                let mut editable_error = errors.lock().unwrap();
+                                editable_error
+                                    .errors
+                                    .push(ScanError::new(dir.clone(), failed));
                             }
-                        } else {
-                            let mut editable_error = errors.lock().unwrap();
-                            editable_error.no_permissions = true
-                        }
-                        None
-                    })
-                    .collect()
-            }
-            Err(failed) => {
-                let mut editable_error = errors.lock().unwrap();
-                match failed.kind() {
-                    std::io::ErrorKind::PermissionDenied => {
-                        editable_error.no_permissions = true;
-                    }
-                    std::io::ErrorKind::NotFound => {
-                        editable_error.file_not_found.insert(failed.to_string());
-                    }
-                    _ => {
-                        editable_error.unknown_error.insert(failed.to_string());
+                            None
+                        })
+                        .collect();
+
+                    // 子ノードが不完全な場合や中断された場合はこのディレクトリのサイズも下限値となる
+                    if partial.load(ORDERING)
+                        || walk_data.cancel_token.is_cancelled()
+                        || children.iter().any(|child| child.is_incomplete())
+                    {
+                        status = NodeStatus::Partial;
+                    } else if let Some(ref md) = dir_md {
+                        let subdirectories = subdirectories.into_inner().unwrap();
+                        update_cache(
+                            walk_data,
+                            md,
+                            cached_files(&children, &subdirectories),
+                            subdirectories,
+                        );
                     }
+                    children
+                }
+                Err(failed) => {
+                    status = NodeStatus::from_error(&failed);
+                    let mut editable_error = errors.lock().unwrap();
+                    editable_error
+                        .errors
+                        .push(ScanError::new(dir.clone(), &failed));
+                    vec![]
                 }
-                vec![]
             }
         }
     } else {
         // dirがファイルの場合
         if !dir.is_file() {
-            // dirがディレクトリでもファイルでも無い場合
+            // dirがディレクトリでもファイルでも無い場合（走査中に削除された場合など）
+            status = NodeStatus::Vanished;
             let mut editable_error = errors.lock().unwrap();
-            let bad_file = dir.as_os_str().to_string_lossy().into();
-            editable_error.file_not_found.insert(bad_file);
+            let failed = std::io::Error::from(std::io::ErrorKind::NotFound);
+            editable_error
+                .errors
+                .push(ScanError::new(dir.clone(), &failed));
         }
         // 空配列をリターン
         vec![]
     };
+    let dir_name = dir.clone();
     let node = build_node(
         dir,
         children,
@@ -188,8 +776,41 @@ fn walk(dir: PathBuf, walk_data: &WalkData, depth: usize) -> Option<Node> {
         walk_data.invert_filter_regex,
         walk_data.use_apparent_size,
         false,
+        walk_data.collect_metadata,
         depth,
-    );
+    )
+    .map(|node| Node {
+        is_dir,
+        via_symlink,
+        status,
+        ..node
+    });
+
+    // 走査中に削除された場合もノードとして残す
+    let node = match (node, status) {
+        (None, NodeStatus::Vanished) => Some(Node {
+            via_symlink,
+            ..Node::vanished(dir_name, depth)
+        }),
+        (node, _) => node,
+    };
+
+    // 指定した深さのディレクトリの走査が完了した場合は途中結果として通知
+    // 子ノードのサイズを集計してから通知する（ハードリンクの重複除去はサブツリー内でのみ行う）
+    if walk_data.stream_depth == Some(depth) {
+        if let Some(ref subtree) = node {
+            let cleaned = clean_inodes(
+                subtree.clone(),
+                &mut HashSet::new(),
+                walk_data.use_apparent_size,
+            );
+            if let Some(cleaned) = cleaned {
+                prog_data.push_subtree(
+                    cleaned.clone_pruned(walk_data.response_depth, &walk_data.stream_prune),
+                );
+            }
+        }
+    }
 
     return node;
 }
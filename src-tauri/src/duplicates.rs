@@ -0,0 +1,220 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use rayon::prelude::*;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::node::Node;
+use crate::platform::get_file_id;
+use crate::progress::ProgressHandler;
+use crate::progress::ScanObserver;
+use crate::progress::ORDERING;
+use crate::progress::{indicator_spawn, indicator_stop};
+
+// 部分ハッシュで読み込むサイズ
+const PARTIAL_HASH_SIZE: usize = 4096; // [byte]
+
+// ハッシュ計算時のバッファサイズ
+const HASH_BUFFER_SIZE: usize = 64 * 1024; // [byte]
+
+type Hash = [u8; 32];
+
+// パス, サイズ, ファイルID
+type FileEntry = (PathBuf, u64, Option<(u64, u64)>);
+
+// 重複ファイルのグループ
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateGroup {
+    pub size: u64,           // 1ファイルあたりのサイズ
+    pub paths: Vec<PathBuf>, // 内容が同一のファイル
+    pub reclaimable: u64,    // 1ファイルを残した場合に削減できるサイズ
+}
+
+// 重複ファイルの検出結果
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DuplicateReport {
+    pub groups: Vec<DuplicateGroup>,
+    pub total_reclaimable: u64,
+}
+
+/* -------------------------------------------------------------------------- */
+
+// ノードから重複の可能性があるファイル（同じサイズのファイルが他にあるもの）を抽出
+pub fn duplicate_candidates(node: &Node, min_size: u64) -> Vec<PathBuf> {
+    let mut by_size: HashMap<u64, Vec<&Path>> = HashMap::new();
+    collect_files(node, min_size.max(1), &mut by_size);
+
+    return by_size
+        .into_values()
+        .filter(|paths| paths.len() > 1)
+        .flatten()
+        .map(Path::to_path_buf)
+        .collect();
+}
+
+fn collect_files<'a>(node: &'a Node, min_size: u64, by_size: &mut HashMap<u64, Vec<&'a Path>>) {
+    // "N small items"ノードは実在するファイルではない
+    if node.is_collapsed() {
+        return;
+    }
+
+    if node.children.is_empty() {
+        if node.size >= min_size {
+            by_size.entry(node.size).or_default().push(&node.name);
+        }
+        return;
+    }
+
+    for child in node.children.iter() {
+        collect_files(child, min_size, by_size);
+    }
+}
+
+// サイズ -> 部分ハッシュ -> 全体ハッシュの順に絞り込んで重複ファイルを検出
+pub fn find_duplicates(
+    candidates: Vec<PathBuf>,
+    progress: &Arc<ProgressHandler>,
+    observer: Arc<dyn ScanObserver>,
+) -> DuplicateReport {
+    progress.clear_state();
+
+    // Progressを表示
+    let indicator_handler = indicator_spawn(progress, observer.clone());
+
+    // 実際のファイルサイズでグループ化（ハードリンクは1つにまとめる）
+    let by_size = group_by_size(candidates);
+
+    // 部分ハッシュでグループ化
+    let by_partial = group_by_hash(by_size, progress, |path, _| {
+        hash_file(path, Some(PARTIAL_HASH_SIZE))
+    });
+
+    // 全体ハッシュでグループ化（部分ハッシュでファイル全体を読んでいる場合は省略）
+    let by_full = group_by_hash(by_partial, progress, |path, size| {
+        if size <= PARTIAL_HASH_SIZE as u64 {
+            Ok([0; 32])
+        } else {
+            hash_file(path, None)
+        }
+    });
+
+    // Progressを終了
+    indicator_stop(indicator_handler);
+    progress.scan_complete.store(true, ORDERING);
+    observer.on_progress(progress);
+
+    let mut groups: Vec<DuplicateGroup> = by_full
+        .into_iter()
+        .map(|(size, mut paths)| {
+            paths.sort();
+            DuplicateGroup {
+                size,
+                reclaimable: size * (paths.len() as u64 - 1),
+                paths,
+            }
+        })
+        .collect();
+    groups.sort_by_key(|group| std::cmp::Reverse(group.reclaimable));
+
+    let total_reclaimable = groups.iter().map(|group| group.reclaimable).sum();
+
+    return DuplicateReport {
+        groups,
+        total_reclaimable,
+    };
+}
+
+/* -------------------------------------------------------------------------- */
+
+// 実際のファイルサイズでグループ化（2つ以上のファイルを含むグループのみ）
+fn group_by_size(candidates: Vec<PathBuf>) -> Vec<(u64, Vec<PathBuf>)> {
+    let files: Vec<FileEntry> = candidates
+        .into_par_iter()
+        .filter_map(|path| {
+            // シンボリックリンクは対象外
+            let md = fs::symlink_metadata(&path).ok()?;
+            if !md.is_file() {
+                return None;
+            }
+            let id = get_file_id(&md);
+            Some((path, md.len(), id))
+        })
+        .collect();
+
+    let mut by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+    let mut seen_ids: HashSet<(u64, u64)> = HashSet::new();
+    for (path, size, id) in files {
+        // 同じinodeを指すハードリンクは重複として扱わない
+        if let Some(id) = id {
+            if !seen_ids.insert(id) {
+                continue;
+            }
+        }
+        by_size.entry(size).or_default().push(path);
+    }
+
+    return by_size
+        .into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .collect();
+}
+
+// グループ内をハッシュで更に分割（2つ以上のファイルを含むグループのみ）
+fn group_by_hash<F>(
+    groups: Vec<(u64, Vec<PathBuf>)>,
+    progress: &Arc<ProgressHandler>,
+    hasher: F,
+) -> Vec<(u64, Vec<PathBuf>)>
+where
+    F: Fn(&Path, u64) -> io::Result<Hash> + Sync,
+{
+    groups
+        .into_par_iter()
+        .flat_map_iter(|(size, paths)| {
+            let hashes: Vec<(Hash, PathBuf)> = paths
+                .into_par_iter()
+                .filter_map(|path| {
+                    let hash = hasher(&path, size).ok()?;
+                    progress.num_files.fetch_add(1, ORDERING);
+                    progress.total_file_size.fetch_add(size, ORDERING);
+                    Some((hash, path))
+                })
+                .collect();
+
+            let mut by_hash: HashMap<Hash, Vec<PathBuf>> = HashMap::new();
+            for (hash, path) in hashes {
+                by_hash.entry(hash).or_default().push(path);
+            }
+
+            by_hash
+                .into_values()
+                .filter(|paths| paths.len() > 1)
+                .map(move |paths| (size, paths))
+        })
+        .collect()
+}
+
+// ファイルのハッシュを計算（limitを指定した場合は先頭のみ）
+fn hash_file(path: &Path, limit: Option<usize>) -> io::Result<Hash> {
+    let file = File::open(path)?;
+    let mut reader: Box<dyn Read> = match limit {
+        Some(limit) => Box::new(file.take(limit as u64)),
+        None => Box::new(file),
+    };
+
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; HASH_BUFFER_SIZE];
+    loop {
+        let len = reader.read(&mut buffer)?;
+        if len == 0 {
+            break;
+        }
+        hasher.update(&buffer[..len]);
+    }
+
+    return Ok(hasher.finalize().into());
+}
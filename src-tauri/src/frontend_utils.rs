@@ -1,8 +1,24 @@
 use opener;
-use std::{fs, path::Path};
+use serde::Serialize;
+use std::sync::Arc;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
 use trash;
 
-use tauri::Manager;
+use tauri::{Emitter, Manager};
+
+use crate::app::get_session;
+use crate::delete_guard::{DeleteGuard, TOKEN_LIFETIME};
+use crate::node::AncestorSize;
+use crate::progress::ProgressHandler;
+use crate::progress::ScanObserver;
+use crate::progress::ORDERING;
+use crate::progress::{indicator_spawn, indicator_stop};
+use crate::tree_watcher::TreeChange;
+use crate::utils::normalize_path;
+use crate::walk_manager::{RemovalResult, ScanSession, WalkManager};
 
 // ファイルマネージャーを開く
 #[tauri::command(rename_all = "snake_case")]
@@ -15,34 +31,347 @@ pub async fn open_file_manager(path: String) -> Result<(), String> {
     }
 }
 
-// ファイルorディレクトリを削除する
+// ファイルorディレクトリを削除する（tokenはprepare_deleteで発行したもの、シンボリックリンクはリンク自体を削除する）
+// 削除したノードをスキャン結果のツリーから取り除き、解放したサイズと更新後の祖先ノードのサイズを返す
 #[tauri::command(rename_all = "snake_case")]
-pub async fn remove_file_or_directory(path: String) -> Result<(), String> {
-    // ディレクトリの場合
-    if Path::new(&path).is_dir() {
-        match fs::remove_dir_all(path) {
-            Ok(ok) => Ok(ok),
-            Err(err) => Err(err.to_string()),
+pub async fn remove_file_or_directory(
+    path: String,
+    token: String,
+    session_id: Option<u64>,
+    state: tauri::State<'_, WalkManager>,
+    app: tauri::AppHandle,
+) -> Result<String, String> {
+    return remove_single(path, token, RemovalMethod::Remove, session_id, &state, app);
+}
+
+// ゴミ箱に移動する（tokenはprepare_deleteで発行したもの）
+// 移動したノードをスキャン結果のツリーから取り除き、解放したサイズと更新後の祖先ノードのサイズを返す
+#[tauri::command(rename_all = "snake_case")]
+pub async fn move_to_trash(
+    path: String,
+    token: String,
+    session_id: Option<u64>,
+    state: tauri::State<'_, WalkManager>,
+    app: tauri::AppHandle,
+) -> Result<String, String> {
+    return remove_single(path, token, RemovalMethod::Trash, session_id, &state, app);
+}
+
+fn remove_single(
+    path: String,
+    token: String,
+    method: RemovalMethod,
+    session_id: Option<u64>,
+    state: &WalkManager,
+    app: tauri::AppHandle,
+) -> Result<String, String> {
+    let session = get_session(state, session_id)?;
+    let paths = vec![normalize_path(path)];
+
+    let guard = state.get_delete_guard();
+    guard.redeem_token(&token, session.get_session_id(), &paths)?;
+
+    let report = remove_batch(&paths, false, method, &session, guard, app);
+
+    match report.items.into_iter().next() {
+        Some(item) if item.success => {
+            let removal = RemovalResult {
+                path: item.path,
+                freed_size: item.freed_size,
+                ancestors: item.ancestors,
+                changes: item.changes,
+            };
+            serde_json::to_string(&removal).map_err(|err| err.to_string())
         }
+        Some(item) => Err(item.error.unwrap_or_default()),
+        None => Err("Nothing to remove".to_string()),
     }
-    // ファイルの場合
-    else {
-        match fs::remove_file(path) {
-            Ok(ok) => Ok(ok),
-            Err(err) => Err(err.to_string()),
+}
+
+// 一括削除の方法
+#[derive(Debug, Clone, Copy)]
+enum RemovalMethod {
+    Remove,
+    Trash,
+}
+
+// 一括削除の各項目の結果
+#[derive(Serialize)]
+struct BatchItemResult {
+    path: PathBuf,
+    success: bool,
+    error: Option<String>,        // 失敗した場合の理由
+    freed_size: u64,              // 解放したサイズ（dry_runの場合は解放されるサイズ） [byte]
+    ancestors: Vec<AncestorSize>, // 更新後の祖先ノードのサイズ（dry_runの場合は空）
+    changes: Vec<TreeChange>,     // 祖先を再走査した場合の変更（dry_runの場合は空）
+}
+
+// 一括削除の結果
+#[derive(Serialize)]
+struct BatchRemovalReport {
+    dry_run: bool,
+    items: Vec<BatchItemResult>,
+    succeeded: usize,
+    failed: usize,
+    total_freed_size: u64, // [byte]
+}
+
+// 削除前の確認結果
+#[derive(Serialize)]
+struct DeletePreparation {
+    token: Option<String>, // 許可したパスの削除に使用するトークン（許可したパスが無い場合はNone）
+    expires_in: u64,       // トークンの有効期間 [s]
+    #[serde(flatten)]
+    report: BatchRemovalReport, // dry_runの結果（successがfalseの項目は削除できない）
+}
+
+// 一括削除の途中経過をWebViewへ通知
+// num_filesは削除したエントリー数、total_file_sizeは削除したファイルの見かけのサイズ
+struct RemovalObserver(tauri::AppHandle);
+
+impl ScanObserver for RemovalObserver {
+    fn on_progress(&self, progress: &ProgressHandler) {
+        let encode_result: Result<String, _> = serde_json::to_string(progress);
+        match encode_result {
+            // 正常にエンコードできた場合はWebViewに送信
+            Ok(str) => self.0.emit("RemovalProgressNotification", str).unwrap(),
+            // エンコードに失敗した場合
+            Err(err) => eprintln!("Progress encode error: {}", err),
         }
     }
 }
 
-// ゴミ箱に移動する
+// 削除前の確認（削除を許可するか確認して解放されるサイズを返し、許可したパスに対して確認用トークンを発行する）
 #[tauri::command(rename_all = "snake_case")]
-pub async fn move_to_trash(path: String) -> Result<(), String> {
-    let result = trash::delete(path);
+pub async fn prepare_delete(
+    paths: Vec<String>,
+    session_id: Option<u64>,
+    state: tauri::State<'_, WalkManager>,
+    app: tauri::AppHandle,
+) -> Result<String, String> {
+    let session = get_session(&state, session_id)?;
+    let paths: Vec<PathBuf> = paths.iter().map(normalize_path).collect();
 
-    match result {
-        Ok(ok) => Ok(ok),
-        Err(err) => Err(err.to_string()),
+    let guard = state.get_delete_guard();
+    let report = remove_batch(&paths, true, RemovalMethod::Remove, &session, guard, app);
+
+    // 許可したパスが無い場合はトークンを発行しない
+    let allowed: Vec<PathBuf> = report
+        .items
+        .iter()
+        .filter(|item| item.success)
+        .map(|item| item.path.clone())
+        .collect();
+    let token = if allowed.is_empty() {
+        None
+    } else {
+        Some(guard.issue_token(session.get_session_id(), allowed))
+    };
+
+    let preparation = DeletePreparation {
+        token,
+        expires_in: TOKEN_LIFETIME,
+        report,
+    };
+
+    return serde_json::to_string(&preparation).map_err(|err| err.to_string());
+}
+
+// 複数のファイルorディレクトリを削除する（dry_runの場合は削除せずに解放されるサイズを返す）
+// dry_runでない場合はprepare_deleteで発行したtokenが必要
+#[tauri::command(rename_all = "snake_case")]
+pub async fn remove_files_or_directories(
+    paths: Vec<String>,
+    dry_run: bool,
+    token: Option<String>,
+    session_id: Option<u64>,
+    state: tauri::State<'_, WalkManager>,
+    app: tauri::AppHandle,
+) -> Result<String, String> {
+    return remove_multiple(
+        paths,
+        dry_run,
+        token,
+        RemovalMethod::Remove,
+        session_id,
+        &state,
+        app,
+    );
+}
+
+// 複数のファイルorディレクトリをゴミ箱に移動する（dry_runの場合は移動せずに解放されるサイズを返す）
+// dry_runでない場合はprepare_deleteで発行したtokenが必要
+#[tauri::command(rename_all = "snake_case")]
+pub async fn move_to_trash_batch(
+    paths: Vec<String>,
+    dry_run: bool,
+    token: Option<String>,
+    session_id: Option<u64>,
+    state: tauri::State<'_, WalkManager>,
+    app: tauri::AppHandle,
+) -> Result<String, String> {
+    return remove_multiple(
+        paths,
+        dry_run,
+        token,
+        RemovalMethod::Trash,
+        session_id,
+        &state,
+        app,
+    );
+}
+
+fn remove_multiple(
+    paths: Vec<String>,
+    dry_run: bool,
+    token: Option<String>,
+    method: RemovalMethod,
+    session_id: Option<u64>,
+    state: &WalkManager,
+    app: tauri::AppHandle,
+) -> Result<String, String> {
+    let session = get_session(state, session_id)?;
+    let paths: Vec<PathBuf> = paths.iter().map(normalize_path).collect();
+
+    let guard = state.get_delete_guard();
+    if !dry_run {
+        let token = token.ok_or_else(|| "Confirmation token is required".to_string())?;
+        guard.redeem_token(&token, session.get_session_id(), &paths)?;
     }
+
+    let report = remove_batch(&paths, dry_run, method, &session, guard, app);
+
+    return serde_json::to_string(&report).map_err(|err| err.to_string());
+}
+
+// 削除を許可しないパスを取得
+#[tauri::command(rename_all = "snake_case")]
+pub fn get_protected_paths(state: tauri::State<'_, WalkManager>) -> Result<String, String> {
+    let paths = state.get_delete_guard().get_protected_paths();
+
+    return serde_json::to_string(&paths).map_err(|err| err.to_string());
+}
+
+// 削除を許可しないパスを設定（pathsを指定しない場合は既定値に戻す）
+#[tauri::command(rename_all = "snake_case")]
+pub fn set_protected_paths(paths: Option<Vec<String>>, state: tauri::State<'_, WalkManager>) {
+    let paths = paths.map(|paths| paths.iter().map(normalize_path).collect());
+
+    state.get_delete_guard().set_protected_paths(paths);
+}
+
+// 順に削除し、失敗した項目があっても残りの項目の削除を続ける
+fn remove_batch(
+    paths: &[PathBuf],
+    dry_run: bool,
+    method: RemovalMethod,
+    session: &ScanSession,
+    guard: &DeleteGuard,
+    app: tauri::AppHandle,
+) -> BatchRemovalReport {
+    // 削除はスキャンのルート以下のみ許可する
+    let root = session.get_info().map(|info| info.root);
+
+    let progress = Arc::new(ProgressHandler {
+        session_id: session.get_session_id(),
+        ..ProgressHandler::default()
+    });
+    let observer: Arc<dyn ScanObserver> = Arc::new(RemovalObserver(app));
+
+    // Progressを表示
+    let indicator_handler = if dry_run {
+        None
+    } else {
+        indicator_spawn(&progress, observer.clone())
+    };
+
+    let items: Vec<BatchItemResult> = paths
+        .iter()
+        .map(|path| {
+            let mut item = BatchItemResult {
+                path: path.clone(),
+                success: false,
+                error: None,
+                freed_size: 0,
+                ancestors: vec![],
+                changes: vec![],
+            };
+
+            // 削除を許可しない場合や存在しない場合
+            let checked = match root {
+                Some(ref root) => guard.check(path, root),
+                None => Err("No scan result".to_string()),
+            };
+            if let Err(err) = checked {
+                item.error = Some(err);
+                return item;
+            }
+
+            if dry_run {
+                item.success = true;
+                item.freed_size = session.measure_path(path);
+                return item;
+            }
+
+            let result = match method {
+                RemovalMethod::Remove => remove_with_progress(path, &progress),
+                RemovalMethod::Trash => trash::delete(path).map_err(|err| err.to_string()),
+            };
+
+            match result {
+                Ok(_) => {
+                    let removal = session.remove_path(path);
+                    item.success = true;
+                    item.freed_size = removal.freed_size;
+                    item.ancestors = removal.ancestors;
+                    item.changes = removal.changes;
+                }
+                Err(err) => {
+                    item.error = Some(err);
+                }
+            }
+            return item;
+        })
+        .collect();
+
+    // Progressを終了
+    indicator_stop(indicator_handler);
+    if !dry_run {
+        progress.scan_complete.store(true, ORDERING);
+        observer.on_progress(&progress);
+    }
+
+    let succeeded = items.iter().filter(|item| item.success).count();
+
+    return BatchRemovalReport {
+        dry_run,
+        succeeded,
+        failed: items.len() - succeeded,
+        total_freed_size: items.iter().map(|item| item.freed_size).sum(),
+        items,
+    };
+}
+
+// 削除しながら削除したエントリー数とファイルサイズをprogressに加算する（シンボリックリンクは辿らない）
+// エラーには削除できなかったパスを含める
+fn remove_with_progress(path: &Path, progress: &ProgressHandler) -> Result<(), String> {
+    let path_error = |err: io::Error| format!("{}: {}", err, path.display());
+
+    let md = fs::symlink_metadata(path).map_err(path_error)?;
+
+    if md.is_dir() {
+        for entry in fs::read_dir(path).map_err(path_error)? {
+            remove_with_progress(&entry.map_err(path_error)?.path(), progress)?;
+        }
+        fs::remove_dir(path).map_err(path_error)?;
+    } else {
+        fs::remove_file(path).map_err(path_error)?;
+        progress.total_file_size.fetch_add(md.len(), ORDERING);
+    }
+
+    progress.num_files.fetch_add(1, ORDERING);
+
+    return Ok(());
 }
 
 // フルディスクアクセスの権限を確認する
@@ -0,0 +1,147 @@
+use globset::GlobBuilder;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::overrides::{Override, OverrideBuilder};
+use std::fmt::Display;
+use std::path::Path;
+
+use crate::walk_error::ParamError;
+
+// 走査中に読み込む無視ファイル
+const IGNORE_FILES: [&str; 2] = [".gitignore", ".ignore"];
+
+// WalkParamsで指定されたglobパターン
+pub struct GlobRules {
+    exclude: Gitignore, // gitignore形式の除外パターン
+    include: Override,  // 指定した場合は一致するファイルのみを集計
+}
+
+impl GlobRules {
+    pub fn new(
+        root: &Path,
+        exclude_patterns: &[String],
+        include_patterns: &[String],
+    ) -> Result<Self, Vec<ParamError>> {
+        let mut errors = vec![];
+
+        let mut exclude = GitignoreBuilder::new(root);
+        for (index, pattern) in exclude_patterns.iter().enumerate() {
+            if let Err(err) = validate_gitignore_glob(pattern) {
+                errors.push(glob_error("exclude_patterns", index, pattern, err));
+            } else if let Err(err) = exclude.add_line(None, pattern) {
+                errors.push(glob_error("exclude_patterns", index, pattern, err));
+            }
+        }
+
+        let mut include = OverrideBuilder::new(root);
+        for (index, pattern) in include_patterns.iter().enumerate() {
+            if let Err(err) = include.add(pattern) {
+                errors.push(glob_error("include_patterns", index, pattern, err));
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        // 個々のパターンは検証済みのため、ここでのエラーはパターン全体に対するもの
+        let exclude = exclude
+            .build()
+            .map_err(|err| vec![glob_set_error("exclude_patterns", err)])?;
+        let include = include
+            .build()
+            .map_err(|err| vec![glob_set_error("include_patterns", err)])?;
+
+        Ok(Self { exclude, include })
+    }
+
+    pub fn is_excluded(&self, path: &Path, is_dir: bool) -> bool {
+        if self.exclude.matched(path, is_dir).is_ignore() {
+            return true;
+        }
+        // ディレクトリは含めるパターンに一致しなくても走査する
+        return self.include.matched(path, is_dir).is_ignore();
+    }
+}
+
+// gitignore形式の1行をglobとして検証（GitignoreBuilderは不正なglobをエラーとせずに読み飛ばすため）
+fn validate_gitignore_glob(pattern: &str) -> Result<(), globset::Error> {
+    // 否定(!)、ルートからの指定(/)、ディレクトリのみの指定(/)を取り除く
+    let glob = pattern.strip_prefix('!').unwrap_or(pattern);
+    let glob = glob.strip_prefix('/').unwrap_or(glob);
+    let glob = glob.strip_suffix('/').unwrap_or(glob);
+
+    GlobBuilder::new(glob)
+        .literal_separator(true)
+        .backslash_escape(true)
+        .build()?;
+    return Ok(());
+}
+
+fn glob_error(field: &str, index: usize, pattern: &str, err: impl Display) -> ParamError {
+    ParamError {
+        field: field.to_string(),
+        index: Some(index),
+        pattern: pattern.to_string(),
+        position: None,
+        message: err.to_string(),
+    }
+}
+
+fn glob_set_error(field: &str, err: ignore::Error) -> ParamError {
+    ParamError {
+        field: field.to_string(),
+        index: None,
+        pattern: "".to_string(),
+        position: None,
+        message: err.to_string(),
+    }
+}
+
+// 走査中のディレクトリから見た無視ファイルの階層（子ディレクトリの規則が優先）
+pub struct IgnoreStack<'a> {
+    parent: Option<&'a IgnoreStack<'a>>,
+    rules: Option<Gitignore>,
+}
+
+impl<'a> IgnoreStack<'a> {
+    pub fn root() -> Self {
+        Self {
+            parent: None,
+            rules: None,
+        }
+    }
+
+    // dirにある.gitignore/.ignoreを読み込んで階層を追加
+    pub fn push(&'a self, dir: &Path) -> IgnoreStack<'a> {
+        let mut builder = GitignoreBuilder::new(dir);
+        for file_name in IGNORE_FILES {
+            let file = dir.join(file_name);
+            if file.is_file() {
+                // 読み込めない行は無視する
+                let _ = builder.add(file);
+            }
+        }
+
+        let rules = builder.build().ok().filter(|rules| !rules.is_empty());
+
+        IgnoreStack {
+            parent: Some(self),
+            rules,
+        }
+    }
+
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let mut stack = Some(self);
+        while let Some(current) = stack {
+            if let Some(ref rules) = current.rules {
+                let matched = rules.matched(path, is_dir);
+                // 否定パターン(!)に一致した場合は無視しない
+                if !matched.is_none() {
+                    return matched.is_ignore();
+                }
+            }
+            stack = current.parent;
+        }
+        return false;
+    }
+}
@@ -1,90 +1,138 @@
+use crate::dir_walker::is_excluded_path;
 use crate::dir_walker::walk_it;
-use crate::dir_walker::WalkData;
-use crate::node::Node;
+use crate::dir_walker::walk_subtree;
+use crate::dir_walker::{VisitedDirectories, WalkData};
+use crate::ignore_rules::GlobRules;
+use crate::node::{Node, PruneOptions};
+use crate::platform::get_device;
+use crate::progress::CancelToken;
 use crate::progress::ErrorHandler;
 use crate::progress::ProgressHandler;
+use crate::progress::ScanObserver;
 use crate::progress::ORDERING;
 use crate::progress::{indicator_spawn, indicator_stop};
+use crate::scan_cache::ScanCache;
 use crate::utils::normalize_path;
+use crate::walk_error::{ParamError, WalkError};
 
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
+use std::fs;
 use std::panic;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::sync::Mutex;
 use sysinfo::{System, SystemExt};
-use tauri::Emitter;
 
 // Rayonのスタックサイズ
 const STACK_SIZE_OF_RAYON: usize = 1024 * 1024 * 1024; // Set stack size to 1024MB
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
 pub struct WalkParams {
     pub target_directory: String,
     pub regex_filter: Option<Vec<String>>,
     pub regex_invert_filter: Option<Vec<String>>,
     pub ignore_directories: Option<Vec<String>>,
+    pub exclude_patterns: Option<Vec<String>>, // gitignore形式のglobパターン（例: "**/node_modules", "*.o", "target/"）
+    pub include_patterns: Option<Vec<String>>, // 指定した場合は一致するファイルのみを集計（例: "*.rs"）
+    #[serde(default)]
+    pub use_ignore_files: bool, // 走査中に見つけた.gitignore/.ignoreに従う
     pub use_apparent_size: bool,
+    #[serde(default)]
+    pub collect_metadata: bool,
+    #[serde(default)]
+    pub same_file_system: bool,
+    #[serde(default)]
+    pub follow_symlinks: bool,
+    pub response_depth: Option<usize>,
+    pub stream_depth: Option<usize>,
+    pub max_depth: Option<usize>, // これより深いノードは親ノードに集約（返却するツリーのみ）
+    pub min_size: Option<u64>, // これより小さいノードは兄弟ノードとまとめる（返却するツリーのみ） [byte]
+    pub min_percentage: Option<f64>, // ルートに対する割合がこれより小さいノードは兄弟ノードとまとめる（返却するツリーのみ） [%]
+    #[serde(default)]
+    pub keep_partial_result: bool, // 中断した場合も途中までの結果を返す
+    pub cache_file: Option<String>, // 指定した場合は前回から更新されていないディレクトリの走査結果を再利用
+}
+
+impl WalkParams {
+    // 結果のツリーの間引き方（閾値はmin_sizeとroot_sizeに対するmin_percentageの大きい方）
+    pub fn prune_options(&self, root_size: u64) -> PruneOptions {
+        let by_size = self.min_size.unwrap_or(0);
+        let by_percentage = match self.min_percentage {
+            Some(percentage) => (root_size as f64 * percentage / 100.0) as u64,
+            None => 0,
+        };
+        return PruneOptions {
+            max_depth: self.max_depth,
+            threshold: by_size.max(by_percentage),
+        };
+    }
 }
 
 pub fn init_walk(
     walk_params: WalkParams,
     errors: &Arc<Mutex<ErrorHandler>>,
     progress: &Arc<ProgressHandler>,
-    app: tauri::AppHandle,
-) -> Option<Node> {
+    cancel_token: &CancelToken,
+    observer: Arc<dyn ScanObserver>,
+) -> Result<Option<Node>, WalkError> {
+    // パラメータを検証
+    let param_errors = validate_walk_params(&walk_params);
+    if !param_errors.is_empty() {
+        return Err(WalkError::invalid_params(param_errors));
+    }
+
     // エラー格納用
     let errors_for_rayon = errors.clone();
-    let errors_final = errors.clone();
 
-    // appのクローン
-    let app_clone = app.clone();
+    // オブザーバーのクローン
+    let observer_clone = observer.clone();
 
     // 以下パラメータ設定
-    let simplified_dir = normalize_path(walk_params.target_directory);
-
-    let filter_regexs = match walk_params.regex_filter {
-        Some(values) => values
-            .iter()
-            .map(|reg| Regex::new(reg).unwrap())
-            .collect::<Vec<Regex>>(),
-        None => vec![],
-    };
+    let simplified_dir = normalize_path(&walk_params.target_directory);
 
-    let invert_filter_regexs = match walk_params.regex_invert_filter {
-        Some(values) => values
-            .iter()
-            .map(|reg| Regex::new(reg).unwrap())
-            .collect::<Vec<Regex>>(),
-        None => vec![],
-    };
+    let filters =
+        compile_filters(&walk_params, &simplified_dir).map_err(WalkError::invalid_params)?;
 
-    let ignore_directories = match walk_params.ignore_directories {
-        Some(values) => values.iter().map(PathBuf::from).collect::<Vec<PathBuf>>(),
-        None => vec![],
-    };
+    let ignored_full_path = ignored_full_path(&walk_params, &simplified_dir);
 
-    let ignored_full_path: HashSet<PathBuf> = ignore_directories
-        .into_iter()
-        .map(|x| simplified_dir.join(&x))
-        .collect();
+    // スキャンキャッシュを読み込む
+    let cache_file = walk_params.cache_file.as_ref().map(normalize_path);
+    let previous_cache = cache_file
+        .as_ref()
+        .map(|path| ScanCache::load(path, &walk_params));
+    let updated_cache = cache_file
+        .as_ref()
+        .map(|_| Mutex::new(ScanCache::new(&walk_params)));
 
     let walk_data = WalkData {
         ignore_directories: ignored_full_path,
-        filter_regex: &filter_regexs,
-        invert_filter_regex: &invert_filter_regexs,
+        filter_regex: &filters.filter_regexs,
+        invert_filter_regex: &filters.invert_filter_regexs,
         use_apparent_size: walk_params.use_apparent_size,
+        collect_metadata: walk_params.collect_metadata,
+        glob_rules: filters.glob_rules,
+        use_ignore_files: walk_params.use_ignore_files,
+        root_device: root_device(&walk_params, &simplified_dir),
+        follow_symlinks: walk_params.follow_symlinks,
+        visited_directories: Mutex::new(VisitedDirectories::default()),
+        stream_depth: walk_params.stream_depth,
+        // ルートのサイズが確定していないため、min_percentageは途中結果には適用しない
+        stream_prune: walk_params.prune_options(0),
+        response_depth: walk_params.response_depth,
         progress_data: progress.clone(),
         errors: errors_for_rayon,
+        cancel_token: cancel_token.clone(),
+        previous_cache,
+        updated_cache,
     };
 
     // Rayonスレッドを作成
     init_rayon();
 
     // Progressを表示
-    let indicator_handler = indicator_spawn(progress, app_clone);
+    let indicator_handler = indicator_spawn(progress, observer_clone);
 
     // Walk
     let top_level_node = walk_it(simplified_dir, &walk_data);
@@ -96,46 +144,178 @@ pub fn init_walk(
     let prog_data = progress.clone();
     prog_data.scan_complete.store(true, ORDERING);
 
-    let encode_result: Result<String, _> = serde_json::to_string(&prog_data);
-    match encode_result {
-        // 正常にエンコードできた場合はWebViewに送信
-        Ok(str) => app.emit("ProgressNotification", str).unwrap(),
-        // エンコードに失敗した場合
-        Err(err) => eprintln!("Progress encode error: {}", err),
-    }
+    observer.on_progress(&prog_data);
 
-    // 強制終了
-    if errors_final.lock().unwrap().abort {
-        println!("Aborting");
-        return None;
+    // 強制終了（途中までの結果を返す場合は中断したディレクトリがPartialとなる）
+    if cancel_token.is_cancelled() {
+        eprintln!("Aborting");
+        if !walk_params.keep_partial_result {
+            return Ok(None);
+        }
     }
 
     // エラー出力
-    let final_errors = walk_data.errors.lock().unwrap();
-    if final_errors.no_permissions {
-        eprintln!("Did not have permissions for all directories");
+    observer.on_errors(&walk_data.errors.lock().unwrap());
+
+    // スキャンキャッシュを保存（中断した場合は保存しない）
+    if let (Some(path), Some(cache)) = (cache_file, walk_data.updated_cache) {
+        if !cancel_token.is_cancelled() {
+            if let Err(err) = cache.into_inner().unwrap().save(&path) {
+                eprintln!("Scan cache save error: {}", err);
+            }
+        }
+    }
+
+    // ノード出力（間引かないツリー、返却する際にprune_optionsで間引く）
+    return Ok(top_level_node);
+}
+
+// スキャン済みのツリーの一部をスキャン時と同じパラメータで再走査（pathのルートからの深さをdepthとする）
+// 除外対象や存在しない場合はNoneを返す
+pub fn rescan_subtree(walk_params: &WalkParams, path: &Path, depth: usize) -> Option<Node> {
+    return with_subtree_walk_data(walk_params, |walk_data, root| {
+        walk_subtree(path.to_path_buf(), walk_data, depth, root)
+    })?;
+}
+
+// 変更されたパスのうち、スキャン時と同じパラメータで走査の対象となるもののみを残す
+// （除外されたディレクトリ以下の変更でツリー全体を再走査しないようにする）
+pub fn retain_scanned_paths(walk_params: &WalkParams, paths: Vec<PathBuf>) -> Vec<PathBuf> {
+    return with_subtree_walk_data(walk_params, |walk_data, root| {
+        paths
+            .into_iter()
+            .filter(|path| !is_excluded_path(path, walk_data, root))
+            .collect()
+    })
+    .unwrap_or_default();
+}
+
+// スキャン時と同じパラメータでツリーの一部を走査するためのWalkDataを作成してfを実行
+fn with_subtree_walk_data<R>(
+    walk_params: &WalkParams,
+    f: impl FnOnce(&WalkData, &Path) -> R,
+) -> Option<R> {
+    let simplified_dir = normalize_path(&walk_params.target_directory);
+
+    let filters = compile_filters(walk_params, &simplified_dir).ok()?;
+
+    let walk_data = WalkData {
+        ignore_directories: ignored_full_path(walk_params, &simplified_dir),
+        filter_regex: &filters.filter_regexs,
+        invert_filter_regex: &filters.invert_filter_regexs,
+        use_apparent_size: walk_params.use_apparent_size,
+        collect_metadata: walk_params.collect_metadata,
+        glob_rules: filters.glob_rules,
+        use_ignore_files: walk_params.use_ignore_files,
+        root_device: root_device(walk_params, &simplified_dir),
+        follow_symlinks: walk_params.follow_symlinks,
+        visited_directories: Mutex::new(VisitedDirectories::default()),
+        stream_depth: None,
+        stream_prune: PruneOptions::default(),
+        response_depth: None,
+        progress_data: Arc::new(ProgressHandler::default()),
+        errors: Arc::new(Mutex::new(ErrorHandler::default())),
+        cancel_token: CancelToken::default(),
+        previous_cache: None,
+        updated_cache: None,
+    };
+
+    return Some(f(&walk_data, &simplified_dir));
+}
+
+// 除外するディレクトリのフルパス
+fn ignored_full_path(walk_params: &WalkParams, root: &Path) -> HashSet<PathBuf> {
+    let ignore_directories = match &walk_params.ignore_directories {
+        Some(values) => values.iter().map(PathBuf::from).collect::<Vec<PathBuf>>(),
+        None => vec![],
+    };
+
+    return ignore_directories
+        .into_iter()
+        .map(|x| root.join(&x))
+        .collect();
+}
+
+// 別のファイルシステムに降りない場合はルートのデバイスIDを取得
+fn root_device(walk_params: &WalkParams, root: &Path) -> Option<u64> {
+    if walk_params.same_file_system {
+        return fs::metadata(root).ok().and_then(|md| get_device(root, &md));
     }
-    if !final_errors.file_not_found.is_empty() {
-        let err = final_errors
-            .file_not_found
-            .iter()
-            .map(|a| a.as_ref())
-            .collect::<Vec<&str>>()
-            .join(", ");
-        eprintln!("No such file or directory: {}", err);
+    return None;
+}
+
+// パラメータを検証（スキャン前やフロントエンドでの入力中に呼び出す）
+pub fn validate_walk_params(walk_params: &WalkParams) -> Vec<ParamError> {
+    let mut errors = vec![];
+
+    let simplified_dir = normalize_path(&walk_params.target_directory);
+    if !simplified_dir.exists() {
+        errors.push(ParamError {
+            field: "target_directory".to_string(),
+            index: None,
+            pattern: walk_params.target_directory.clone(),
+            position: None,
+            message: "No such file or directory".to_string(),
+        });
     }
-    if !final_errors.unknown_error.is_empty() {
-        let err = final_errors
-            .unknown_error
-            .iter()
-            .map(|a| a.as_ref())
-            .collect::<Vec<&str>>()
-            .join(", ");
-        eprintln!("Unknown Error: {}", err);
+
+    if let Err(filter_errors) = compile_filters(walk_params, &simplified_dir) {
+        errors.extend(filter_errors);
     }
 
-    // ノード出力
-    return top_level_node;
+    return errors;
+}
+
+// コンパイル済みのフィルター
+struct Filters {
+    filter_regexs: Vec<Regex>,
+    invert_filter_regexs: Vec<Regex>,
+    glob_rules: GlobRules,
+}
+
+fn compile_filters(walk_params: &WalkParams, root: &Path) -> Result<Filters, Vec<ParamError>> {
+    let mut errors = vec![];
+
+    let filter_regexs = compile_regexs("regex_filter", &walk_params.regex_filter, &mut errors);
+    let invert_filter_regexs = compile_regexs(
+        "regex_invert_filter",
+        &walk_params.regex_invert_filter,
+        &mut errors,
+    );
+
+    let glob_rules = GlobRules::new(
+        root,
+        walk_params.exclude_patterns.as_deref().unwrap_or_default(),
+        walk_params.include_patterns.as_deref().unwrap_or_default(),
+    );
+
+    match glob_rules {
+        Ok(glob_rules) if errors.is_empty() => Ok(Filters {
+            filter_regexs,
+            invert_filter_regexs,
+            glob_rules,
+        }),
+        Ok(_) => Err(errors),
+        Err(glob_errors) => {
+            errors.extend(glob_errors);
+            Err(errors)
+        }
+    }
+}
+
+fn compile_regexs(
+    field: &str,
+    values: &Option<Vec<String>>,
+    errors: &mut Vec<ParamError>,
+) -> Vec<Regex> {
+    let mut regexs = vec![];
+    for (index, reg) in values.iter().flatten().enumerate() {
+        match Regex::new(reg) {
+            Ok(regex) => regexs.push(regex),
+            Err(err) => errors.push(ParamError::from_regex(field, index, reg, err)),
+        }
+    }
+    return regexs;
 }
 
 fn init_rayon() {
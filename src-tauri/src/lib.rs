@@ -1,21 +1,49 @@
+mod age_breakdown;
+#[cfg(feature = "gui")]
+mod app;
+mod delete_guard;
+mod diff;
 mod dir_walker;
+mod duplicates;
+#[cfg(feature = "gui")]
 mod frontend_utils;
+mod ignore_rules;
 mod init_walk;
 mod node;
 mod platform;
 mod progress;
+mod scan_cache;
+mod snapshot;
+mod top_n;
+mod tree_watcher;
+mod type_breakdown;
 mod utils;
+mod walk_error;
+#[cfg(feature = "gui")]
 mod walk_manager;
 
-use tauri::Manager;
-
-use crate::frontend_utils::{
-    check_full_disk_access_permission, move_to_trash, open_file_manager, remove_file_or_directory,
+pub use crate::age_breakdown::{age_breakdown, AgeBreakdown, AgeBucket};
+#[cfg(feature = "gui")]
+pub use crate::app::run;
+pub use crate::delete_guard::{DeleteGuard, TOKEN_LIFETIME};
+pub use crate::diff::{diff_nodes, DiffNode, DiffStatus};
+pub use crate::duplicates::{
+    duplicate_candidates, find_duplicates, DuplicateGroup, DuplicateReport,
+};
+pub use crate::init_walk::WalkParams;
+pub use crate::init_walk::{init_walk, rescan_subtree, retain_scanned_paths, validate_walk_params};
+pub use crate::node::{AncestorSize, FileType, Node, NodeMetadata, NodeStatus, PruneOptions};
+pub use crate::progress::ProgressHandler;
+pub use crate::progress::ScanObserver;
+pub use crate::progress::{CancelToken, ErrorHandler, ScanError, ScanErrorKind};
+pub use crate::snapshot::{load_snapshot, save_snapshot, ScanInfo, Snapshot};
+pub use crate::top_n::{top_n, SizeEntry, TopNFilter, TopNReport};
+pub use crate::tree_watcher::{
+    apply_rescan, plan_rescans, watch_tree, TreeChange, TreeWatcher, WatchBatch,
 };
-use crate::init_walk::init_walk;
-use crate::init_walk::WalkParams;
-use crate::node::Node;
-use crate::walk_manager::WalkManager;
+pub use crate::type_breakdown::{type_breakdown, TypeBreakdown, TypeGroup};
+pub use crate::utils::unix_now;
+pub use crate::walk_error::{ParamError, WalkError};
 
 // ノードをjsonに変換
 pub fn node_to_json(node: Option<Node>) -> Result<String, String> {
@@ -38,117 +66,3 @@ pub fn node_to_json(node: Option<Node>) -> Result<String, String> {
         None => Ok("".to_string()),
     }
 }
-
-// Walk Start（asyncで非同期とする）
-#[tauri::command(rename_all = "snake_case")]
-async fn walk_start(
-    str_params: &str,
-    state: tauri::State<'_, WalkManager>,
-    app: tauri::AppHandle,
-) -> Result<String, String> {
-    // 終了フラグをfalseに設定
-    state.set_abort_flag(false);
-
-    let decode_result: Result<WalkParams, _> = serde_json::from_str(&str_params);
-
-    match decode_result {
-        // 正常にパラメータをデコードできた場合
-        Ok(walk_params) => {
-            // Walk
-            let walk_data = init_walk(
-                walk_params,
-                state.get_error_handler(),
-                state.get_progress_handler(),
-                app,
-            );
-
-            // 現状使用しないので無効化
-            /*
-            // Walk Dataのクローン
-            let walk_data_clone = walk_data.clone();
-
-            // ノードをセット
-            state.set_node(walk_data_clone);
-            */
-
-            // ノードをjsonに変換
-            return node_to_json(walk_data);
-        }
-        // パラメータのデコードに失敗した場合
-        Err(err) => {
-            eprintln!("Parameter decode error: {}", err.to_string());
-            Err(err.to_string())
-        }
-    }
-}
-
-// 現状使用しないので無効化
-/*
-// ノードをリロード（asyncで非同期とする）
-#[tauri::command(rename_all = "snake_case")]
-async fn node_reload(state: tauri::State<'_, WalkManager>) -> Result<String, String> {
-    // ノードを取得
-    let node = state.get_node();
-
-    // ノードをjsonに変換
-    return node_to_json(node);
-}
-*/
-
-// 強制終了
-#[tauri::command(rename_all = "snake_case")]
-fn abort(state: tauri::State<'_, WalkManager>) {
-    // 終了フラグをtrueに設定
-    state.set_abort_flag(true);
-}
-
-#[cfg_attr(mobile, tauri::mobile_entry_point)]
-pub fn run() {
-    tauri::Builder::default()
-        .setup(move |app| {
-            let walk_manager = WalkManager::new();
-            app.manage(walk_manager);
-
-            // MacOSのみメニューを生成
-            #[cfg(target_os = "macos")]
-            {
-                use tauri::menu::{AboutMetadata, Menu, SubmenuBuilder};
-
-                let menu = Menu::new(app)?;
-
-                let mut submenu = SubmenuBuilder::new(app, "File")
-                    .about_with_text("About", Some(AboutMetadata::default()))
-                    .separator()
-                    .quit_with_text("Quit")
-                    .build()?;
-
-                menu.append(&submenu)?;
-
-                submenu = SubmenuBuilder::new(app, "Window")
-                    .minimize()
-                    .separator()
-                    .close_window()
-                    .build()?;
-
-                menu.append(&submenu)?;
-
-                app.set_menu(menu)?;
-            }
-
-            Ok(())
-        })
-        .plugin(tauri_plugin_shell::init())
-        .plugin(tauri_plugin_dialog::init())
-        .plugin(tauri_plugin_clipboard_manager::init())
-        .plugin(tauri_plugin_opener::init())
-        .invoke_handler(tauri::generate_handler![
-            walk_start,
-            abort,
-            remove_file_or_directory,
-            open_file_manager,
-            check_full_disk_access_permission,
-            move_to_trash
-        ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
-}
@@ -1,18 +1,327 @@
+use crate::platform::get_extended_metadata;
 use crate::platform::get_metadata;
 use crate::utils::is_filtered_out_due_to_invert_regex;
 use crate::utils::is_filtered_out_due_to_regex;
 
 use regex::Regex;
-use serde::Serialize;
-use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Eq, PartialEq, Clone, Serialize)]
+#[derive(Debug, Eq, PartialEq, Clone, Deserialize, Serialize)]
 pub struct Node {
     pub name: PathBuf,
     pub size: u64,
     pub children: Vec<Node>,
     pub inode_device: Option<(u64, u64)>,
     pub depth: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Box<NodeMetadata>>, // WalkParams::collect_metadataが有効な場合のみ
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_dir: bool, // ディレクトリ（辿ったシンボリックリンクを含む）
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub via_symlink: bool, // シンボリックリンクを辿って到達したノード
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collapsed_items: Option<usize>, // 小さい項目をまとめた合成ノードの場合はまとめた項目数（実在するパスではない）
+    #[serde(default, skip_serializing_if = "NodeStatus::is_ok")]
+    pub status: NodeStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub num_children: Option<usize>, // 子ノードを省略して複製した場合の子ノードの数（get_subtree/get_childrenで取得できる）
+}
+
+// ディレクトリの読み込み状態（Ok以外の場合はサイズが下限値となる）
+#[derive(Debug, Default, Eq, PartialEq, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeStatus {
+    #[default]
+    Ok,
+    PermissionDenied, // ディレクトリを読み込めなかった
+    Partial,          // 一部のエントリーまたは子ディレクトリを読み込めなかった
+    Vanished,         // 走査中に削除された
+}
+
+impl NodeStatus {
+    pub fn from_error(err: &io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::PermissionDenied => NodeStatus::PermissionDenied,
+            io::ErrorKind::NotFound => NodeStatus::Vanished,
+            _ => NodeStatus::Partial,
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        return *self == NodeStatus::Ok;
+    }
+}
+
+// 結果のツリーの間引き方（保持しているツリーは間引かず、返却や通知する複製にのみ適用する）
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PruneOptions {
+    pub max_depth: Option<usize>, // これより深いノードは親ノードに集約
+    pub threshold: u64, // これより小さい兄弟ノードは"N small items"ノードにまとめる [byte]
+}
+
+// ツリーを更新した際の祖先ノードのサイズ
+#[derive(Debug, Clone, Serialize)]
+pub struct AncestorSize {
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+// ファイルの種類
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileType {
+    File,
+    Directory,
+    Symlink,
+    Other,
+}
+
+// ノードの拡張メタデータ
+#[derive(Debug, Eq, PartialEq, Clone, Deserialize, Serialize)]
+pub struct NodeMetadata {
+    pub file_type: FileType,
+    pub modified: Option<u64>, // UNIX時間 [s]
+    pub accessed: Option<u64>, // UNIX時間 [s]
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    pub owner: Option<String>, // uidから解決したユーザー名
+    pub group: Option<String>, // gidから解決したグループ名
+    pub mode: Option<u32>,     // パーミッション（Unixのみ）
+}
+
+impl Node {
+    // 走査中に削除されたノード
+    pub fn vanished(name: PathBuf, depth: usize) -> Node {
+        Node {
+            name,
+            size: 0,
+            children: vec![],
+            inode_device: None,
+            depth,
+            metadata: None,
+            is_dir: false,
+            via_symlink: false,
+            collapsed_items: None,
+            status: NodeStatus::Vanished,
+            num_children: None,
+        }
+    }
+
+    // 小さい項目をまとめた合成ノード
+    fn collapsed(parent: &Node, small: &[&Node]) -> Node {
+        Node {
+            name: parent.name.join(format!("{} small items", small.len())),
+            size: small.iter().map(|child| child.size).sum(),
+            children: vec![],
+            inode_device: None,
+            depth: parent.depth + 1,
+            metadata: None,
+            is_dir: false,
+            via_symlink: false,
+            collapsed_items: Some(small.len()),
+            status: if small.iter().any(|child| child.is_incomplete()) {
+                NodeStatus::Partial
+            } else {
+                NodeStatus::Ok
+            },
+            num_children: None,
+        }
+    }
+
+    // 小さい項目をまとめた合成ノードかどうか（集計やパスの探索では対象外とする）
+    pub fn is_collapsed(&self) -> bool {
+        return self.collapsed_items.is_some();
+    }
+
+    // サイズが下限値かどうか（読み込めなかったディレクトリを含む）
+    pub fn is_incomplete(&self) -> bool {
+        return matches!(
+            self.status,
+            NodeStatus::PermissionDenied | NodeStatus::Partial
+        );
+    }
+
+    // ディレクトリかどうか（is_dirを持たない古いスナップショットの場合は子ノードの有無で判定する）
+    pub fn is_directory(&self) -> bool {
+        return self.is_dir || !self.children.is_empty();
+    }
+
+    // パスに一致するノードを探索
+    pub fn find(&self, path: &Path) -> Option<&Node> {
+        if self.name == path {
+            return Some(self);
+        }
+
+        // パスを含む子ノードのみを辿る
+        self.children
+            .iter()
+            .find(|child| !child.is_collapsed() && path.starts_with(&child.name))
+            .and_then(|child| child.find(path))
+    }
+
+    // パスに一致するノードを探索（変更用）
+    pub fn find_mut(&mut self, path: &Path) -> Option<&mut Node> {
+        if self.name == path {
+            return Some(self);
+        }
+
+        self.children
+            .iter_mut()
+            .find(|child| !child.is_collapsed() && path.starts_with(&child.name))
+            .and_then(|child| child.find_mut(path))
+    }
+
+    // パスを含むノードのうち最も深いノードを探索（パス自体が存在しない場合は祖先ノード）
+    pub fn find_deepest(&self, path: &Path) -> Option<&Node> {
+        if !path.starts_with(&self.name) {
+            return None;
+        }
+
+        match self
+            .children
+            .iter()
+            .find(|child| !child.is_collapsed() && path.starts_with(&child.name))
+        {
+            Some(child) => child.find_deepest(path),
+            None => Some(self),
+        }
+    }
+
+    // パスに一致する子孫ノードを削除し、祖先ノードのサイズから差し引く（削除したノードを返す）
+    pub fn remove_descendant(&mut self, path: &Path) -> Option<Node> {
+        let index = self
+            .children
+            .iter()
+            .position(|child| !child.is_collapsed() && path.starts_with(&child.name))?;
+
+        let removed = if self.children[index].name == path {
+            self.children.remove(index)
+        } else {
+            self.children[index].remove_descendant(path)?
+        };
+
+        self.size = self.size.saturating_sub(removed.size);
+
+        return Some(removed);
+    }
+
+    // 子孫ノードを追加または置き換え、祖先ノードのサイズを更新（親ノードが存在しない場合はfalse）
+    pub fn upsert_descendant(&mut self, node: Node) -> bool {
+        return self.upsert_descendant_with_delta(node).is_some();
+    }
+
+    fn upsert_descendant_with_delta(&mut self, node: Node) -> Option<i64> {
+        let delta = if node.name.parent() == Some(self.name.as_path()) {
+            match self
+                .children
+                .iter_mut()
+                .find(|child| !child.is_collapsed() && child.name == node.name)
+            {
+                Some(child) => {
+                    let delta = node.size as i64 - child.size as i64;
+                    *child = node;
+                    delta
+                }
+                None => {
+                    let delta = node.size as i64;
+                    self.children.push(node);
+                    delta
+                }
+            }
+        } else {
+            self.children
+                .iter_mut()
+                .find(|child| !child.is_collapsed() && node.name.starts_with(&child.name))?
+                .upsert_descendant_with_delta(node)?
+        };
+
+        self.size = self.size.saturating_add_signed(delta);
+
+        return Some(delta);
+    }
+
+    // ルートからパスの親ノードまでの祖先ノードのサイズ
+    pub fn ancestor_sizes(&self, path: &Path) -> Vec<AncestorSize> {
+        let mut ancestors = vec![];
+        let mut current = Some(self);
+        while let Some(node) = current {
+            if node.name == path || !path.starts_with(&node.name) {
+                break;
+            }
+            ancestors.push(AncestorSize {
+                path: node.name.clone(),
+                size: node.size,
+            });
+            current = node
+                .children
+                .iter()
+                .find(|child| !child.is_collapsed() && path.starts_with(&child.name));
+        }
+        return ancestors;
+    }
+
+    // 指定した深さまでの子ノードを含めて複製（子ノードを省略した場合はその数を残す）
+    pub fn clone_with_depth(&self, depth: usize) -> Node {
+        return self.clone_pruned(Some(depth), &PruneOptions::default());
+    }
+
+    // 間引いて複製（depthを指定した場合はその深さまでの子ノードを含め、省略した子ノードの数を残す）
+    // max_depthより深いノードは含めず、閾値未満の兄弟ノードは"N small items"ノードにまとめる
+    pub fn clone_pruned(&self, depth: Option<usize>, prune: &PruneOptions) -> Node {
+        let children: Vec<&Node> = if prune
+            .max_depth
+            .is_some_and(|max_depth| self.depth >= max_depth)
+        {
+            vec![]
+        } else {
+            self.children.iter().collect()
+        };
+
+        let (small, mut large): (Vec<&Node>, Vec<&Node>) = children
+            .into_iter()
+            .partition(|child| child.size < prune.threshold);
+
+        // 1つだけの場合はまとめない
+        let collapsed = if small.len() == 1 {
+            large.extend(small);
+            None
+        } else if !small.is_empty() {
+            Some(Node::collapsed(self, &small))
+        } else {
+            None
+        };
+
+        let num_children = large.len() + collapsed.iter().count();
+
+        let (children, num_children) = match depth {
+            Some(0) if num_children > 0 => (vec![], Some(num_children)),
+            Some(0) => (vec![], self.num_children),
+            _ => {
+                let mut children: Vec<Node> = large
+                    .into_iter()
+                    .map(|child| child.clone_pruned(depth.map(|depth| depth - 1), prune))
+                    .collect();
+                children.extend(collapsed);
+                (children, self.num_children)
+            }
+        };
+
+        Node {
+            name: self.name.clone(),
+            size: self.size,
+            children,
+            inode_device: self.inode_device,
+            depth: self.depth,
+            metadata: self.metadata.clone(),
+            is_dir: self.is_dir,
+            via_symlink: self.via_symlink,
+            collapsed_items: self.collapsed_items,
+            status: self.status,
+            num_children,
+        }
+    }
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -23,6 +332,7 @@ pub fn build_node(
     invert_filter_regex: &[Regex],
     use_apparent_size: bool,
     is_symlink: bool,
+    collect_metadata: bool,
     depth: usize,
 ) -> Option<Node> {
     get_metadata(&dir, use_apparent_size).map(|data| {
@@ -41,12 +351,153 @@ pub fn build_node(
             data.0
         };
 
+        let metadata = if collect_metadata {
+            get_extended_metadata(&dir).map(Box::new)
+        } else {
+            None
+        };
+
         Node {
             name: dir,
             size,
             children,
             inode_device,
             depth,
+            metadata,
+            is_dir: false,
+            via_symlink: false,
+            collapsed_items: None,
+            status: NodeStatus::Ok,
+            num_children: None,
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(name: &str, size: u64, depth: usize) -> Node {
+        Node {
+            name: PathBuf::from(name),
+            size,
+            depth,
+            status: NodeStatus::Ok,
+            ..Node::vanished(PathBuf::new(), 0)
+        }
+    }
+
+    fn dir(name: &str, depth: usize, children: Vec<Node>) -> Node {
+        Node {
+            size: children.iter().map(|child| child.size).sum(),
+            children,
+            is_dir: true,
+            ..file(name, 0, depth)
+        }
+    }
+
+    // /r (350)
+    // ├── /r/a (300)
+    // │   ├── /r/a/f1 (100)
+    // │   └── /r/a/f2 (200)
+    // └── /r/g (50)
+    fn sample_tree() -> Node {
+        dir(
+            "/r",
+            0,
+            vec![
+                dir(
+                    "/r/a",
+                    1,
+                    vec![file("/r/a/f1", 100, 2), file("/r/a/f2", 200, 2)],
+                ),
+                file("/r/g", 50, 1),
+            ],
+        )
+    }
+
+    fn size_of(tree: &Node, path: &str) -> Option<u64> {
+        tree.find(Path::new(path)).map(|node| node.size)
+    }
+
+    #[test]
+    fn remove_descendant_subtracts_from_ancestors() {
+        let mut tree = sample_tree();
+
+        let removed = tree.remove_descendant(Path::new("/r/a/f1")).unwrap();
+
+        assert_eq!(removed.name, PathBuf::from("/r/a/f1"));
+        assert_eq!(removed.size, 100);
+        assert_eq!(size_of(&tree, "/r/a/f1"), None);
+        assert_eq!(size_of(&tree, "/r/a"), Some(200));
+        assert_eq!(size_of(&tree, "/r"), Some(250));
+    }
+
+    #[test]
+    fn remove_descendant_removes_whole_subtree() {
+        let mut tree = sample_tree();
+
+        let removed = tree.remove_descendant(Path::new("/r/a")).unwrap();
+
+        assert_eq!(removed.children.len(), 2);
+        assert_eq!(size_of(&tree, "/r/a/f2"), None);
+        assert_eq!(size_of(&tree, "/r"), Some(50));
+    }
+
+    #[test]
+    fn remove_descendant_ignores_missing_paths() {
+        let mut tree = sample_tree();
+
+        assert!(tree.remove_descendant(Path::new("/r/a/missing")).is_none());
+        assert!(tree.remove_descendant(Path::new("/other/f")).is_none());
+        // ルート自体は子孫ではない
+        assert!(tree.remove_descendant(Path::new("/r")).is_none());
+        assert_eq!(tree, sample_tree());
+    }
+
+    #[test]
+    fn upsert_descendant_replaces_existing_node() {
+        let mut tree = sample_tree();
+
+        assert!(tree.upsert_descendant(file("/r/a/f2", 20, 2)));
+
+        assert_eq!(size_of(&tree, "/r/a/f2"), Some(20));
+        assert_eq!(size_of(&tree, "/r/a"), Some(120));
+        assert_eq!(size_of(&tree, "/r"), Some(170));
+        assert_eq!(tree.find(Path::new("/r/a")).unwrap().children.len(), 2);
+    }
+
+    #[test]
+    fn upsert_descendant_adds_new_node() {
+        let mut tree = sample_tree();
+
+        let new_dir = dir("/r/a/d", 2, vec![file("/r/a/d/f3", 400, 3)]);
+        assert!(tree.upsert_descendant(new_dir));
+
+        assert_eq!(size_of(&tree, "/r/a/d/f3"), Some(400));
+        assert_eq!(size_of(&tree, "/r/a"), Some(700));
+        assert_eq!(size_of(&tree, "/r"), Some(750));
+    }
+
+    #[test]
+    fn upsert_descendant_requires_parent() {
+        let mut tree = sample_tree();
+
+        // 親ノード（/r/missing）がツリーに無い
+        assert!(!tree.upsert_descendant(file("/r/missing/f", 10, 2)));
+        // ルート外
+        assert!(!tree.upsert_descendant(file("/other/f", 10, 1)));
+        assert_eq!(tree, sample_tree());
+    }
+
+    #[test]
+    fn remove_then_upsert_restores_sizes() {
+        let mut tree = sample_tree();
+
+        let removed = tree.remove_descendant(Path::new("/r/a")).unwrap();
+        assert!(tree.upsert_descendant(removed));
+
+        assert_eq!(size_of(&tree, "/r"), Some(350));
+        assert_eq!(size_of(&tree, "/r/a/f1"), Some(100));
+    }
+}
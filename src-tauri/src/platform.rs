@@ -2,6 +2,9 @@
 use std::fs;
 
 use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::node::{FileType, NodeMetadata};
 
 #[cfg(target_family = "unix")]
 fn get_block_size() -> u64 {
@@ -25,6 +28,209 @@ pub fn get_metadata(d: &Path, use_apparent_size: bool) -> Option<(u64, Option<(u
     }
 }
 
+// 拡張メタデータを取得（シンボリックリンクはリンク自体の情報）
+pub fn get_extended_metadata(d: &Path) -> Option<NodeMetadata> {
+    let md = d.symlink_metadata().ok()?;
+
+    let file_type = if md.file_type().is_symlink() {
+        FileType::Symlink
+    } else if md.is_dir() {
+        FileType::Directory
+    } else if md.is_file() {
+        FileType::File
+    } else {
+        FileType::Other
+    };
+
+    let (uid, gid, mode) = get_owner_and_mode(&md);
+
+    Some(NodeMetadata {
+        file_type,
+        modified: md.modified().ok().and_then(to_unix_time),
+        accessed: md.accessed().ok().and_then(to_unix_time),
+        uid,
+        gid,
+        owner: None, // 走査後にまとめて解決する
+        group: None, // 走査後にまとめて解決する
+        mode,
+    })
+}
+
+fn to_unix_time(time: SystemTime) -> Option<u64> {
+    time.duration_since(UNIX_EPOCH).ok().map(|d| d.as_secs())
+}
+
+#[cfg(target_family = "unix")]
+fn get_owner_and_mode(md: &fs::Metadata) -> (Option<u32>, Option<u32>, Option<u32>) {
+    use std::os::unix::fs::MetadataExt;
+    (Some(md.uid()), Some(md.gid()), Some(md.mode()))
+}
+
+#[cfg(target_family = "windows")]
+fn get_owner_and_mode(_md: &fs::Metadata) -> (Option<u32>, Option<u32>, Option<u32>) {
+    // Windows has no uid/gid or mode bits, ownership is expressed with SIDs
+    (None, None, None)
+}
+
+// uidからユーザー名を取得
+#[cfg(target_family = "unix")]
+pub fn get_user_name(uid: u32) -> Option<String> {
+    use std::ffi::CStr;
+
+    let mut buf = vec![0 as libc::c_char; 16 * 1024];
+    let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result: *mut libc::passwd = std::ptr::null_mut();
+
+    let ret =
+        unsafe { libc::getpwuid_r(uid, &mut passwd, buf.as_mut_ptr(), buf.len(), &mut result) };
+    if ret != 0 || result.is_null() {
+        return None;
+    }
+
+    let name = unsafe { CStr::from_ptr(passwd.pw_name) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+#[cfg(target_family = "windows")]
+pub fn get_user_name(_uid: u32) -> Option<String> {
+    None
+}
+
+// gidからグループ名を取得
+#[cfg(target_family = "unix")]
+pub fn get_group_name(gid: u32) -> Option<String> {
+    use std::ffi::CStr;
+
+    let mut buf = vec![0 as libc::c_char; 16 * 1024];
+    let mut group: libc::group = unsafe { std::mem::zeroed() };
+    let mut result: *mut libc::group = std::ptr::null_mut();
+
+    let ret =
+        unsafe { libc::getgrgid_r(gid, &mut group, buf.as_mut_ptr(), buf.len(), &mut result) };
+    if ret != 0 || result.is_null() {
+        return None;
+    }
+
+    let name = unsafe { CStr::from_ptr(group.gr_name) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+#[cfg(target_family = "windows")]
+pub fn get_group_name(_gid: u32) -> Option<String> {
+    None
+}
+
+// ファイルシステムのデバイスID
+#[cfg(target_family = "unix")]
+pub fn get_device(_path: &Path, md: &fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(md.dev())
+}
+
+#[cfg(target_family = "windows")]
+pub fn get_device(path: &Path, _md: &fs::Metadata) -> Option<u64> {
+    // stdのメタデータからは取得できないため、開いてボリュームのシリアル番号を取得する
+    return file_information(path).map(|info| info.volume_serial_number());
+}
+
+// マウントポイント（ファイルシステムのルート）かどうか
+pub fn is_mount_point(path: &Path) -> bool {
+    // 相対パスやシンボリックリンクの場合に正しい親ディレクトリと比較するため正規化
+    let Ok(path) = fs::canonicalize(path) else {
+        return false;
+    };
+    let device = fs::metadata(&path)
+        .ok()
+        .and_then(|md| get_device(&path, &md));
+    let parent_device = match path.parent() {
+        Some(parent) => fs::metadata(parent)
+            .ok()
+            .and_then(|md| get_device(parent, &md)),
+        None => return true, // ルートディレクトリ
+    };
+    return device.is_some() && device != parent_device;
+}
+
+// ファイルシステムの使用量 [byte]
+#[cfg(target_family = "unix")]
+pub fn get_used_space(path: &Path) -> Option<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+
+    let ret = unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) };
+    if ret != 0 {
+        return None;
+    }
+
+    #[allow(clippy::unnecessary_cast)]
+    let used = (stat.f_blocks as u64).saturating_sub(stat.f_bfree as u64) * stat.f_frsize as u64;
+    Some(used)
+}
+
+#[cfg(target_family = "windows")]
+pub fn get_used_space(_path: &Path) -> Option<u64> {
+    None
+}
+
+// パスが長すぎることによるエラーかどうか
+#[cfg(target_family = "unix")]
+pub fn is_path_too_long(err: &std::io::Error) -> bool {
+    err.raw_os_error() == Some(libc::ENAMETOOLONG)
+}
+
+#[cfg(target_family = "windows")]
+pub fn is_path_too_long(err: &std::io::Error) -> bool {
+    const ERROR_FILENAME_EXCED_RANGE: i32 = 206;
+    err.raw_os_error() == Some(ERROR_FILENAME_EXCED_RANGE)
+}
+
+// ハードリンクを判定するためのID（inode, device）
+#[cfg(target_family = "unix")]
+pub fn get_file_id(md: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((md.ino(), md.dev()))
+}
+
+#[cfg(target_family = "windows")]
+pub fn get_file_id(_md: &fs::Metadata) -> Option<(u64, u64)> {
+    // The file index is not available from std on stable, see get_metadata
+    None
+}
+
+// 循環検出のためのディレクトリのID（inode, device）
+#[cfg(target_family = "unix")]
+pub fn get_directory_id(_path: &Path, md: &fs::Metadata) -> Option<(u64, u64)> {
+    return get_file_id(md);
+}
+
+#[cfg(target_family = "windows")]
+pub fn get_directory_id(path: &Path, _md: &fs::Metadata) -> Option<(u64, u64)> {
+    // stdのメタデータからは取得できないため、ディレクトリを開いてファイルインデックスを取得する
+    // （ディレクトリのみのため、get_metadataで避けているファイルを開くコストは問題にならない）
+    let info = file_information(path)?;
+    return Some((info.file_index(), info.volume_serial_number()));
+}
+
+// 属性の読み取りのみで開いてファイルの情報を取得（リンクは辿る）
+#[cfg(target_family = "windows")]
+fn file_information(path: &Path) -> Option<winapi_util::file::Information> {
+    use std::fs::OpenOptions;
+    use std::os::windows::fs::OpenOptionsExt;
+    use winapi_util::file::information;
+    use winapi_util::Handle;
+    const FILE_READ_ATTRIBUTES: u32 = 0x0080;
+
+    let file = OpenOptions::new()
+        .access_mode(FILE_READ_ATTRIBUTES)
+        .open(path)
+        .ok()?;
+    let h = Handle::from_file(file);
+    return information(&h).ok();
+}
+
 #[cfg(target_family = "windows")]
 pub fn get_metadata(d: &Path, use_apparent_size: bool) -> Option<(u64, Option<(u64, u64)>)> {
     // On windows opening the file to get size, file ID and volume can be very
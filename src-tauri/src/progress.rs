@@ -1,16 +1,18 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashSet,
+    io,
+    path::PathBuf,
     sync::{
         atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
         mpsc::{self, RecvTimeoutError, Sender},
-        Arc,
+        Arc, Mutex,
     },
     thread::JoinHandle,
     time::Duration,
 };
 
-use tauri::Emitter;
+use crate::node::Node;
+use crate::platform::is_path_too_long;
 
 // インジケーターの更新間隔
 const INDICATOR_UPDATE_INTERVAL: u64 = 100; // [ms]
@@ -19,11 +21,53 @@ pub const ORDERING: Ordering = Ordering::Relaxed;
 
 /* -------------------------------------------------------------------------- */
 
+// スキャンの途中経過や結果を受け取るオブザーバー
+// WebViewへの通知やCLIでの出力はこのトレイトを実装して行う
+pub trait ScanObserver: Send + Sync {
+    // 途中経過（INDICATOR_UPDATE_INTERVAL毎と完了時に呼ばれる）
+    fn on_progress(&self, progress: &ProgressHandler);
+
+    // 走査が完了したサブツリー（stream_depthが指定されている場合のみ）
+    fn on_subtrees(&self, _subtrees: &[Node]) {}
+
+    // 走査終了時のエラー
+    fn on_errors(&self, errors: &ErrorHandler) {
+        for error in errors.errors.iter() {
+            eprintln!("{}: {}", error.message, error.path.display());
+        }
+        for mount_point in errors.skipped_mount_points.iter() {
+            eprintln!("Skipped mount point: {}", mount_point.display());
+        }
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+
+// スキャンの中断用トークン（スキャン毎に作成する）
+#[derive(Debug, Default, Clone)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, ORDERING);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        return self.0.load(ORDERING);
+    }
+}
+
+/* -------------------------------------------------------------------------- */
+
 #[derive(Default, Serialize)]
 pub struct ProgressHandler {
+    pub session_id: u64,    // スキャンを実行しているセッションのID
+    pub scan_id: AtomicU64, // 実行中のスキャンのID（abort時に指定する）
     pub num_files: AtomicUsize,
     pub total_file_size: AtomicU64,
     pub scan_complete: AtomicBool,
+    #[serde(skip)]
+    pub completed_subtrees: Mutex<Vec<Node>>,
 }
 
 impl ProgressHandler {
@@ -31,15 +75,70 @@ impl ProgressHandler {
         self.total_file_size.store(0, ORDERING);
         self.num_files.store(0, ORDERING);
         self.scan_complete.store(false, ORDERING);
+        self.completed_subtrees.lock().unwrap().clear();
+    }
+
+    // 走査が完了したサブツリーを追加
+    pub fn push_subtree(&self, node: Node) {
+        self.completed_subtrees.lock().unwrap().push(node);
+    }
+
+    // 走査が完了したサブツリーを取り出す
+    pub fn take_subtrees(&self) -> Vec<Node> {
+        std::mem::take(&mut *self.completed_subtrees.lock().unwrap())
+    }
+}
+
+// エラーの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScanErrorKind {
+    PermissionDenied,
+    NotFound,
+    PathTooLong,
+    Io,
+}
+
+// 走査中に発生したエラー
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ScanError {
+    pub path: PathBuf,
+    pub kind: ScanErrorKind,
+    pub message: String,
+    #[serde(default)]
+    pub counted_size: Option<u64>, // 読み込めなかったディレクトリで集計できたサイズ（実際のサイズの下限値、ツリーに存在する場合のみ） [byte]
+}
+
+impl ScanError {
+    pub fn new(path: PathBuf, err: &io::Error) -> Self {
+        let kind = match err.kind() {
+            io::ErrorKind::PermissionDenied => ScanErrorKind::PermissionDenied,
+            io::ErrorKind::NotFound => ScanErrorKind::NotFound,
+            _ if is_path_too_long(err) => ScanErrorKind::PathTooLong,
+            _ => ScanErrorKind::Io,
+        };
+
+        Self {
+            path,
+            kind,
+            message: err.to_string(),
+            counted_size: None,
+        }
     }
 }
 
 #[derive(Default)]
 pub struct ErrorHandler {
-    pub no_permissions: bool,
-    pub file_not_found: HashSet<String>,
-    pub unknown_error: HashSet<String>,
-    pub abort: bool,
+    pub errors: Vec<ScanError>,
+    pub skipped_mount_points: Vec<PathBuf>, // same_file_system有効時に除外したマウントポイント
+}
+
+impl ErrorHandler {
+    // 前回のスキャン結果をクリア
+    pub fn clear_state(&mut self) {
+        self.errors.clear();
+        self.skipped_mount_points.clear();
+    }
 }
 
 /* -------------------------------------------------------------------------- */
@@ -47,7 +146,7 @@ pub struct ErrorHandler {
 // Progressを表示
 pub fn indicator_spawn(
     progress: &Arc<ProgressHandler>,
-    app: tauri::AppHandle,
+    observer: Arc<dyn ScanObserver>,
 ) -> Option<(Sender<()>, JoinHandle<()>)> {
     let prog_data = progress.clone();
 
@@ -59,19 +158,25 @@ pub fn indicator_spawn(
         while let Err(RecvTimeoutError::Timeout) =
             receiver.recv_timeout(Duration::from_millis(INDICATOR_UPDATE_INTERVAL))
         {
-            let encode_result: Result<String, _> = serde_json::to_string(&prog_data);
-            match encode_result {
-                // 正常にエンコードできた場合はWebViewに送信
-                Ok(str) => app.emit("ProgressNotification", str).unwrap(),
-                // エンコードに失敗した場合
-                Err(err) => eprintln!("Progress encode error: {}", err),
-            }
+            observer.on_progress(&prog_data);
+            notify_subtrees(&prog_data, observer.as_ref());
         }
+
+        // 終了時に残っているサブツリーを通知
+        notify_subtrees(&prog_data, observer.as_ref());
     });
 
     return Some((sender, indicator_thread));
 }
 
+// 走査が完了したサブツリーを通知
+fn notify_subtrees(prog_data: &ProgressHandler, observer: &dyn ScanObserver) {
+    let subtrees = prog_data.take_subtrees();
+    if !subtrees.is_empty() {
+        observer.on_subtrees(&subtrees);
+    }
+}
+
 // Progressを終了
 pub fn indicator_stop(thread: Option<(Sender<()>, JoinHandle<()>)>) {
     match thread {
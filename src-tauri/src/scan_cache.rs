@@ -0,0 +1,145 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::init_walk::WalkParams;
+use crate::node::Node;
+use crate::platform::get_file_id;
+use crate::snapshot::{read_compressed_json, write_compressed_json};
+
+// キャッシュのフォーマットバージョン（互換性の無い変更を行った場合に更新する）
+pub const CACHE_VERSION: u32 = 1;
+
+// 更新日時がスキャン開始直前のディレクトリはキャッシュしない（同じ更新日時のまま変更される可能性がある）
+const RACY_INTERVAL: u64 = 2_000_000_000; // [ns]
+
+// キャッシュしたディレクトリの内容
+// ディレクトリの更新日時はエントリーの追加・削除・名前の変更でのみ更新されるため、
+// ファイルの上書きによるサイズの変化は検出できない点に注意
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CachedDirectory {
+    pub modified: u64,                    // 更新日時 [ns]
+    pub files: Vec<Node>,                 // ファイル（nameはファイル名のみ）
+    pub subdirectories: Vec<CachedEntry>, // サブディレクトリ（再スキャン時は再帰的に走査する）
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CachedEntry {
+    pub name: PathBuf,    // ディレクトリ名のみ
+    pub is_symlink: bool, // follow_symlinksで辿ったシンボリックリンク
+}
+
+// ディレクトリの(device, inode)をキーとしたキャッシュ
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct ScanCache {
+    version: u32,
+    fingerprint: String, // キャッシュ作成時のパラメータ（異なる場合はキャッシュを使用しない）
+    directories: HashMap<String, CachedDirectory>,
+    #[serde(skip)]
+    started: u64, // スキャン開始日時 [ns]
+}
+
+impl ScanCache {
+    pub fn new(params: &WalkParams) -> Self {
+        Self {
+            version: CACHE_VERSION,
+            fingerprint: fingerprint(params),
+            directories: HashMap::new(),
+            started: to_nanos(SystemTime::now()).unwrap_or(0),
+        }
+    }
+
+    // キャッシュファイルを読み込み（存在しない場合やパラメータが異なる場合は空のキャッシュ）
+    pub fn load(path: &Path, params: &WalkParams) -> Self {
+        let empty = Self::new(params);
+
+        match read_compressed_json::<ScanCache>(path) {
+            Ok(cache)
+                if cache.version == CACHE_VERSION && cache.fingerprint == empty.fingerprint =>
+            {
+                return Self {
+                    started: empty.started,
+                    ..cache
+                };
+            }
+            Ok(_) => {
+                eprintln!("Scan cache is outdated: {}", path.display());
+            }
+            Err(err) if path.exists() => {
+                eprintln!("Scan cache load error: {}", err);
+            }
+            Err(_) => {}
+        }
+
+        return empty;
+    }
+
+    // キャッシュファイルを保存
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        return write_compressed_json(path, self);
+    }
+
+    // 更新日時が一致する場合のみキャッシュを取得
+    pub fn lookup(&self, md: &fs::Metadata) -> Option<&CachedDirectory> {
+        let cached = self.directories.get(&cache_key(md)?)?;
+        if Some(cached.modified) != md.modified().ok().and_then(to_nanos) {
+            return None;
+        }
+        return Some(cached);
+    }
+
+    // ディレクトリの内容を追加
+    pub fn insert(
+        &mut self,
+        md: &fs::Metadata,
+        files: Vec<Node>,
+        subdirectories: Vec<CachedEntry>,
+    ) {
+        let (Some(key), Some(modified)) = (cache_key(md), md.modified().ok().and_then(to_nanos))
+        else {
+            return;
+        };
+
+        if modified + RACY_INTERVAL > self.started {
+            return;
+        }
+
+        self.directories.insert(
+            key,
+            CachedDirectory {
+                modified,
+                files,
+                subdirectories,
+            },
+        );
+    }
+}
+
+// 走査結果に影響するパラメータ
+fn fingerprint(params: &WalkParams) -> String {
+    let values = (
+        &params.target_directory,
+        &params.regex_filter,
+        &params.regex_invert_filter,
+        &params.ignore_directories,
+        &params.exclude_patterns,
+        &params.include_patterns,
+        params.use_ignore_files,
+        params.use_apparent_size,
+        params.collect_metadata,
+        params.same_file_system,
+        params.follow_symlinks,
+    );
+    return serde_json::to_string(&values).unwrap_or_default();
+}
+
+fn cache_key(md: &fs::Metadata) -> Option<String> {
+    get_file_id(md).map(|(inode, device)| format!("{}:{}", device, inode))
+}
+
+fn to_nanos(time: SystemTime) -> Option<u64> {
+    let duration = time.duration_since(UNIX_EPOCH).ok()?;
+    return u64::try_from(duration.as_nanos()).ok();
+}
@@ -0,0 +1,179 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::init_walk::WalkParams;
+use crate::node::Node;
+use crate::platform::{get_used_space, is_mount_point};
+use crate::progress::{ErrorHandler, ScanError};
+use crate::utils::{normalize_path, unix_now};
+
+// スナップショットのフォーマットバージョン（互換性の無い変更を行った場合に更新する）
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+// スキャンの実行情報
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ScanInfo {
+    pub root: PathBuf,
+    pub timestamp: u64, // UNIX時間 [s]
+    pub params: WalkParams,
+    #[serde(default)]
+    pub skipped_mount_points: Vec<PathBuf>, // same_file_system有効時に除外したマウントポイント
+    #[serde(default)]
+    pub errors: Vec<ScanError>, // 走査中に発生したエラー
+    #[serde(default)]
+    pub cancelled: bool, // 中断したスキャンの途中結果の場合はtrue
+    #[serde(default)]
+    pub unaccounted_size: Option<u64>, // 集計できなかったサイズ（マウントポイントを走査した場合のみ、ファイルシステムの使用量との差） [byte]
+    #[serde(default)]
+    pub errors_counted_size: Option<u64>, // エラーの発生したディレクトリで集計できたサイズの合計（実際のサイズの下限値） [byte]
+}
+
+impl ScanInfo {
+    // 現在時刻で作成
+    pub fn new(params: WalkParams) -> Self {
+        Self {
+            root: normalize_path(&params.target_directory),
+            timestamp: unix_now(),
+            params,
+            skipped_mount_points: vec![],
+            errors: vec![],
+            cancelled: false,
+            unaccounted_size: None,
+            errors_counted_size: None,
+        }
+    }
+
+    // スキャン終了時のエラーハンドラとノードから結果を取得
+    pub fn set_result(&mut self, errors: &ErrorHandler, node: Option<&Node>) {
+        self.skipped_mount_points = errors.skipped_mount_points.clone();
+        self.skipped_mount_points.sort();
+
+        self.errors = errors.errors.clone();
+        self.errors.sort_by(|a, b| a.path.cmp(&b.path));
+
+        // エラーの発生したディレクトリのうち読み込めなかったもので集計できたサイズ
+        if let Some(node) = node {
+            for error in self.errors.iter_mut() {
+                error.counted_size = node
+                    .find(&error.path)
+                    .filter(|found| found.is_incomplete())
+                    .map(|found| found.size);
+            }
+        }
+
+        // ディスク使用量で比較できる場合のみ（見かけのサイズやフィルターを使用した場合は比較できない）
+        let comparable = !self.params.use_apparent_size
+            && is_empty(&self.params.regex_filter)
+            && is_empty(&self.params.regex_invert_filter)
+            && is_empty(&self.params.ignore_directories)
+            && is_empty(&self.params.exclude_patterns)
+            && is_empty(&self.params.include_patterns)
+            && !self.params.use_ignore_files;
+
+        // 実際に集計できなかったサイズはマウントポイントの場合のみファイルシステムの使用量から求められる
+        self.unaccounted_size = match node {
+            Some(node) if comparable && !self.cancelled && is_mount_point(&self.root) => {
+                get_used_space(&self.root).map(|used| used.saturating_sub(node.size))
+            }
+            _ => None,
+        };
+        self.errors_counted_size = self.sum_errors_counted_size();
+    }
+
+    // エラーの発生したディレクトリで集計できたサイズの合計（入れ子になっている場合は外側のみ）
+    fn sum_errors_counted_size(&self) -> Option<u64> {
+        let mut total = None;
+        let mut last_counted: Option<&Path> = None;
+        // パスでソート済みのため祖先は直前に集計したパスのみを確認すればよい
+        for error in self.errors.iter() {
+            let Some(size) = error.counted_size else {
+                continue;
+            };
+            if last_counted.is_some_and(|last| error.path.starts_with(last)) {
+                continue;
+            }
+            total = Some(total.unwrap_or(0) + size);
+            last_counted = Some(&error.path);
+        }
+        return total;
+    }
+}
+
+fn is_empty(values: &Option<Vec<String>>) -> bool {
+    values.iter().flatten().next().is_none()
+}
+
+// 書き込み用（ノードをクローンせずにシリアライズする）
+#[derive(Serialize)]
+struct SnapshotRef<'a> {
+    version: u32,
+    info: &'a ScanInfo,
+    node: &'a Node,
+}
+
+// 読み込み用
+#[derive(Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub info: ScanInfo,
+    pub node: Node,
+}
+
+// スナップショットを保存（gzip圧縮したjson）
+pub fn save_snapshot(path: &Path, info: &ScanInfo, node: &Node) -> Result<(), String> {
+    let snapshot = SnapshotRef {
+        version: SNAPSHOT_VERSION,
+        info,
+        node,
+    };
+    return write_compressed_json(path, &snapshot);
+}
+
+// スナップショットを読み込み
+pub fn load_snapshot(path: &Path) -> Result<Snapshot, String> {
+    let snapshot: Snapshot = read_compressed_json(path)?;
+
+    // 新しいバージョンで保存されたスナップショットは読み込まない
+    if snapshot.version > SNAPSHOT_VERSION {
+        return Err(format!(
+            "Unsupported snapshot version: {} (supported: {})",
+            snapshot.version, SNAPSHOT_VERSION
+        ));
+    }
+
+    return Ok(snapshot);
+}
+
+// gzip圧縮したjsonとして書き込み（スキャンキャッシュでも使用）
+pub fn write_compressed_json<T: Serialize>(path: &Path, value: &T) -> Result<(), String> {
+    let file = File::create(path).map_err(|err| err.to_string())?;
+    let encoder = GzEncoder::new(BufWriter::new(file), Compression::default());
+
+    // serde_jsonは細かく書き込むため、圧縮前のデータもバッファリングする
+    let mut writer = BufWriter::new(encoder);
+    serde_json::to_writer(&mut writer, value).map_err(|err| err.to_string())?;
+
+    writer
+        .into_inner()
+        .map_err(|err| err.to_string())?
+        .finish()
+        .map_err(|err| err.to_string())?;
+
+    return Ok(());
+}
+
+// gzip圧縮したjsonを読み込み
+pub fn read_compressed_json<T: DeserializeOwned>(path: &Path) -> Result<T, String> {
+    let file = File::open(path).map_err(|err| err.to_string())?;
+    // 展開後のデータもバッファリングする
+    let decoder = BufReader::new(GzDecoder::new(BufReader::new(file)));
+
+    return serde_json::from_reader(decoder).map_err(|err| err.to_string());
+}
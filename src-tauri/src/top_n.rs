@@ -0,0 +1,149 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+use serde::Serialize;
+
+use crate::node::Node;
+
+// サイズ順の項目
+#[derive(Debug, Clone, Serialize)]
+pub struct SizeEntry {
+    pub name: PathBuf,
+    pub size: u64,
+}
+
+// サイズの大きいファイル・ディレクトリ（いずれもサイズの降順）
+#[derive(Debug, Clone, Serialize)]
+pub struct TopNReport {
+    pub files: Vec<SizeEntry>,
+    pub directories: Vec<SizeEntry>, // サブディレクトリを含むサイズ
+    pub directories_own: Vec<SizeEntry>, // 直下のファイルのみのサイズ
+}
+
+// 絞り込み条件
+#[derive(Debug, Default)]
+pub struct TopNFilter {
+    pub extensions: Vec<String>, // ファイルの拡張子（小文字、空の場合は全て）
+    pub regex: Option<Regex>,    // フルパスに一致するもののみ
+}
+
+impl TopNFilter {
+    fn matches_file(&self, path: &Path) -> bool {
+        if !self.extensions.is_empty() {
+            let extension = path
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            if !self.extensions.contains(&extension) {
+                return false;
+            }
+        }
+        return self.matches_path(path);
+    }
+
+    fn matches_path(&self, path: &Path) -> bool {
+        match self.regex {
+            Some(ref regex) => regex.is_match(&path.as_os_str().to_string_lossy()),
+            None => true,
+        }
+    }
+}
+
+// 上位n件のみを保持するヒープ（ノード数に関わらずメモリはnに比例）
+struct BoundedHeap<'a> {
+    n: usize,
+    heap: BinaryHeap<Reverse<(u64, &'a Path)>>,
+}
+
+impl<'a> BoundedHeap<'a> {
+    fn new(n: usize) -> Self {
+        Self {
+            n,
+            heap: BinaryHeap::with_capacity(n + 1),
+        }
+    }
+
+    fn push(&mut self, size: u64, path: &'a Path) {
+        if self.n == 0 {
+            return;
+        }
+        // 既に満杯で最小値以下の場合は追加しない
+        if self.heap.len() == self.n && self.heap.peek().is_some_and(|min| min.0 .0 >= size) {
+            return;
+        }
+        self.heap.push(Reverse((size, path)));
+        if self.heap.len() > self.n {
+            self.heap.pop();
+        }
+    }
+
+    fn into_entries(self) -> Vec<SizeEntry> {
+        self.heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(|Reverse((size, path))| SizeEntry {
+                name: path.to_path_buf(),
+                size,
+            })
+            .collect()
+    }
+}
+
+// ノード以下のサイズの大きいファイル・ディレクトリをn件ずつ取得（間引かないツリーに対して使用する）
+pub fn top_n(node: &Node, n: usize, filter: &TopNFilter) -> TopNReport {
+    let mut files = BoundedHeap::new(n);
+    let mut directories = BoundedHeap::new(n);
+    let mut directories_own = BoundedHeap::new(n);
+
+    collect(
+        node,
+        filter,
+        &mut files,
+        &mut directories,
+        &mut directories_own,
+    );
+
+    return TopNReport {
+        files: files.into_entries(),
+        directories: directories.into_entries(),
+        directories_own: directories_own.into_entries(),
+    };
+}
+
+fn collect<'a>(
+    node: &'a Node,
+    filter: &TopNFilter,
+    files: &mut BoundedHeap<'a>,
+    directories: &mut BoundedHeap<'a>,
+    directories_own: &mut BoundedHeap<'a>,
+) {
+    // "N small items"ノードは実在するファイルではない
+    if node.is_collapsed() {
+        return;
+    }
+
+    if !node.is_directory() {
+        if filter.matches_file(&node.name) {
+            files.push(node.size, &node.name);
+        }
+        return;
+    }
+
+    if filter.matches_path(&node.name) {
+        let subdirectories_size: u64 = node
+            .children
+            .iter()
+            .filter(|child| child.is_directory())
+            .map(|child| child.size)
+            .sum();
+
+        directories.push(node.size, &node.name);
+        directories_own.push(node.size.saturating_sub(subdirectories_size), &node.name);
+    }
+
+    for child in node.children.iter() {
+        collect(child, filter, files, directories, directories_own);
+    }
+}
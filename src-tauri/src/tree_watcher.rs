@@ -0,0 +1,206 @@
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use crate::node::{AncestorSize, Node};
+
+// 最後のイベントからこの時間が経過したらまとめて反映する
+const DEBOUNCE_INTERVAL: u64 = 500; // [ms]
+
+// イベントが続く場合でもこの時間が経過したら反映する
+const MAX_DEBOUNCE_DELAY: u64 = 5000; // [ms]
+
+/* -------------------------------------------------------------------------- */
+
+// 監視で検出した変更（デバウンス済み）
+#[derive(Debug, Default)]
+pub struct WatchBatch {
+    pub paths: Vec<PathBuf>, // 変更されたパス（祖先のパスが含まれる場合は祖先のみ）
+    pub overflow: bool,      // イベントが溢れて取りこぼした場合はtrue
+}
+
+// ツリーに反映した変更
+#[derive(Debug, Clone, Serialize)]
+pub struct TreeChange {
+    pub path: PathBuf,
+    pub node: Option<Node>, // 変更後のノード（子ノードは含まない、削除された場合はNone）
+    pub ancestors: Vec<AncestorSize>, // 変更後の祖先ノードのサイズ（ルートから順）
+}
+
+// ファイルシステムの監視（破棄すると監視を終了する）
+pub struct TreeWatcher {
+    _watcher: RecommendedWatcher,
+}
+
+// root以下を再帰的に監視し、デバウンスした変更をon_batchに渡す
+pub fn watch_tree(
+    root: &Path,
+    mut on_batch: impl FnMut(WatchBatch) + Send + 'static,
+) -> Result<TreeWatcher, String> {
+    let (sender, receiver) = mpsc::channel::<notify::Result<Event>>();
+
+    let mut watcher = notify::recommended_watcher(sender).map_err(|err| err.to_string())?;
+    watcher
+        .watch(root, RecursiveMode::Recursive)
+        .map_err(|err| err.to_string())?;
+
+    // ウォッチャーが破棄されるとsenderも破棄され、スレッドが終了する
+    std::thread::spawn(move || {
+        while let Ok(event) = receiver.recv() {
+            let mut batch = WatchBatch::default();
+            add_event(&mut batch, event);
+
+            // イベントが途切れるまでまとめる
+            let started = Instant::now();
+            let mut disconnected = false;
+            while started.elapsed() < Duration::from_millis(MAX_DEBOUNCE_DELAY) {
+                match receiver.recv_timeout(Duration::from_millis(DEBOUNCE_INTERVAL)) {
+                    Ok(event) => add_event(&mut batch, event),
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => {
+                        disconnected = true;
+                        break;
+                    }
+                }
+            }
+
+            if disconnected {
+                break;
+            }
+
+            batch.paths = topmost_paths(batch.paths);
+            if batch.overflow || !batch.paths.is_empty() {
+                on_batch(batch);
+            }
+        }
+    });
+
+    return Ok(TreeWatcher { _watcher: watcher });
+}
+
+fn add_event(batch: &mut WatchBatch, event: notify::Result<Event>) {
+    match event {
+        Ok(event) => {
+            if event.need_rescan() {
+                batch.overflow = true;
+            }
+            // 読み込みのみのイベントはサイズに影響しない
+            if !matches!(event.kind, EventKind::Access(_)) {
+                batch.paths.extend(event.paths);
+            }
+        }
+        Err(err) => {
+            eprintln!("Watch error: {}", err);
+        }
+    }
+}
+
+// 祖先のパスが含まれるパスを除外
+fn topmost_paths(mut paths: Vec<PathBuf>) -> Vec<PathBuf> {
+    paths.sort();
+    paths.dedup();
+
+    let mut topmost: Vec<PathBuf> = vec![];
+    for path in paths {
+        // ソート済みのため祖先は直前に追加したパスのみを確認すればよい
+        if topmost.last().is_some_and(|last| path.starts_with(last)) {
+            continue;
+        }
+        topmost.push(path);
+    }
+    return topmost;
+}
+
+/* -------------------------------------------------------------------------- */
+
+// 再走査するパスとそのルートからの深さ
+// 親ノードがツリーに無い場合は、ツリーに存在する最も深い祖先を再走査する
+// 親ノードの子が"N small items"ノードにまとめられている場合（間引いて保存したスナップショット）も親ノードを再走査する
+// イベントが溢れた場合はルート直下のエントリーを全て再走査する
+pub fn plan_rescans(node: &Node, batch: &WatchBatch) -> Vec<(PathBuf, usize)> {
+    if batch.overflow {
+        let mut paths: Vec<PathBuf> = node
+            .children
+            .iter()
+            .map(|child| child.name.clone())
+            .collect();
+        if let Ok(entries) = fs::read_dir(&node.name) {
+            paths.extend(
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.path()),
+            );
+        }
+        paths.sort();
+        paths.dedup();
+        return paths
+            .into_iter()
+            .map(|path| (path, node.depth + 1))
+            .collect();
+    }
+
+    let mut targets = vec![];
+    for path in batch.paths.iter() {
+        let Some(anchor) = node.find_deepest(path) else {
+            // ルート外のパス
+            continue;
+        };
+
+        // "N small items"ノードにまとめられている可能性がある場合は、二重に集計しないよう親ごと再走査する
+        let has_collapsed = anchor.children.iter().any(|child| child.is_collapsed());
+
+        if anchor.name == *path {
+            targets.push((path.clone(), anchor.depth));
+        } else if path.parent() == Some(anchor.name.as_path())
+            && anchor.is_directory()
+            && !has_collapsed
+        {
+            targets.push((path.clone(), anchor.depth + 1));
+        } else {
+            targets.push((anchor.name.clone(), anchor.depth));
+        }
+    }
+
+    // 祖先を再走査する場合は子孫の再走査は不要
+    let topmost: HashSet<PathBuf> =
+        topmost_paths(targets.iter().map(|(path, _)| path.clone()).collect())
+            .into_iter()
+            .collect();
+    targets.sort();
+    targets.dedup();
+    targets.retain(|(path, _)| topmost.contains(path));
+    return targets;
+}
+
+// 再走査した結果をツリーに反映（変更が無い場合はNone）
+pub fn apply_rescan(node: &mut Node, path: &Path, rescanned: Option<Node>) -> Option<TreeChange> {
+    if node.find(path) == rescanned.as_ref() {
+        return None;
+    }
+
+    if node.name == path {
+        // ルート自体が削除された場合は削除済みとして残す
+        *node = rescanned.unwrap_or_else(|| Node::vanished(path.to_path_buf(), node.depth));
+    } else {
+        match rescanned {
+            Some(rescanned) => {
+                if !node.upsert_descendant(rescanned) {
+                    return None;
+                }
+            }
+            None => {
+                node.remove_descendant(path)?;
+            }
+        }
+    }
+
+    return Some(TreeChange {
+        path: path.to_path_buf(),
+        node: node.find(path).map(|node| node.clone_with_depth(0)),
+        ancestors: node.ancestor_sizes(path),
+    });
+}
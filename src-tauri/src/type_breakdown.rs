@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::node::Node;
+
+// 分類と対応する拡張子
+const CATEGORIES: [(&str, &[&str]); 8] = [
+    (
+        "media",
+        &[
+            "mp4", "mkv", "mov", "avi", "wmv", "webm", "m4v", "mpg", "mpeg", "mp3", "wav", "flac",
+            "aac", "ogg", "m4a", "jpg", "jpeg", "png", "gif", "bmp", "tif", "tiff", "heic", "webp",
+            "raw", "cr2", "nef", "psd", "svg",
+        ],
+    ),
+    (
+        "archives",
+        &[
+            "zip", "tar", "gz", "tgz", "bz2", "xz", "zst", "7z", "rar", "lz4", "cab", "jar", "war",
+        ],
+    ),
+    (
+        "code",
+        &[
+            "rs", "c", "h", "cc", "cpp", "hpp", "cs", "java", "kt", "go", "py", "rb", "php", "js",
+            "mjs", "ts", "tsx", "jsx", "vue", "swift", "m", "sh", "ps1", "html", "css", "scss",
+            "json", "yaml", "yml", "toml", "xml", "sql",
+        ],
+    ),
+    (
+        "binaries",
+        &[
+            "exe", "dll", "so", "dylib", "a", "lib", "o", "obj", "rlib", "class", "pyc", "wasm",
+            "bin", "msi", "deb", "rpm", "pkg", "apk", "app",
+        ],
+    ),
+    (
+        "vm_images",
+        &[
+            "iso", "img", "dmg", "vmdk", "vdi", "vhd", "vhdx", "qcow2", "ova", "ovf", "hdd",
+        ],
+    ),
+    (
+        "documents",
+        &[
+            "pdf", "doc", "docx", "xls", "xlsx", "ppt", "pptx", "odt", "ods", "odp", "txt", "md",
+            "rtf", "csv", "epub",
+        ],
+    ),
+    ("logs", &["log", "out", "err", "trace"]),
+    (
+        "databases",
+        &["db", "sqlite", "sqlite3", "mdb", "ldb", "mdf", "ibd"],
+    ),
+];
+
+// このディレクトリ以下のファイルは拡張子に関わらず依存パッケージとして分類する
+const DEPENDENCY_DIRECTORIES: [&str; 4] =
+    ["node_modules", "bower_components", "site-packages", ".venv"];
+
+// 依存パッケージの分類名
+const DEPENDENCIES: &str = "dependencies";
+
+// どの分類にも該当しない場合の分類名
+const OTHER: &str = "other";
+
+// 拡張子または分類毎の集計
+#[derive(Debug, Clone, Serialize)]
+pub struct TypeGroup {
+    pub name: String, // 拡張子（小文字、拡張子が無い場合は空文字）または分類名
+    pub size: u64,
+    pub count: u64,
+}
+
+// 種類別の使用量の内訳（いずれもサイズの降順）
+#[derive(Debug, Clone, Serialize)]
+pub struct TypeBreakdown {
+    pub by_extension: Vec<TypeGroup>,
+    pub by_category: Vec<TypeGroup>,
+}
+
+// ノード以下のファイルを拡張子と分類で集計（ディレクトリ自体のサイズは含めない）
+pub fn type_breakdown(node: &Node) -> TypeBreakdown {
+    let mut by_extension: HashMap<String, (u64, u64)> = HashMap::new();
+    let mut by_category: HashMap<&'static str, (u64, u64)> = HashMap::new();
+
+    // 対象のノード自体が依存パッケージ内にある場合
+    let in_dependencies =
+        is_dependency_directory(&node.name) || node.name.ancestors().any(is_dependency_directory);
+
+    collect(node, in_dependencies, &mut by_extension, &mut by_category);
+
+    return TypeBreakdown {
+        by_extension: into_groups(by_extension),
+        by_category: into_groups(by_category),
+    };
+}
+
+fn collect(
+    node: &Node,
+    in_dependencies: bool,
+    by_extension: &mut HashMap<String, (u64, u64)>,
+    by_category: &mut HashMap<&'static str, (u64, u64)>,
+) {
+    // "N small items"ノードは実在するファイルではないため集計しない
+    if node.is_collapsed() {
+        return;
+    }
+
+    if node.is_directory() {
+        let in_dependencies = in_dependencies || is_dependency_directory(&node.name);
+        for child in node.children.iter() {
+            collect(child, in_dependencies, by_extension, by_category);
+        }
+        return;
+    }
+
+    let extension = node
+        .name
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    let category = if in_dependencies {
+        DEPENDENCIES
+    } else {
+        category_of(&extension)
+    };
+
+    let entry = by_category.entry(category).or_default();
+    entry.0 += node.size;
+    entry.1 += 1;
+
+    let entry = by_extension.entry(extension).or_default();
+    entry.0 += node.size;
+    entry.1 += 1;
+}
+
+fn is_dependency_directory(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| DEPENDENCY_DIRECTORIES.iter().any(|dir| name == *dir))
+}
+
+fn category_of(extension: &str) -> &'static str {
+    CATEGORIES
+        .iter()
+        .find(|(_, extensions)| extensions.contains(&extension))
+        .map_or(OTHER, |(category, _)| category)
+}
+
+fn into_groups<K: ToString>(map: HashMap<K, (u64, u64)>) -> Vec<TypeGroup> {
+    let mut groups: Vec<TypeGroup> = map
+        .into_iter()
+        .map(|(name, (size, count))| TypeGroup {
+            name: name.to_string(),
+            size,
+            count,
+        })
+        .collect();
+    groups.sort_by_key(|group| std::cmp::Reverse(group.size));
+    return groups;
+}
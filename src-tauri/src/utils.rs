@@ -1,4 +1,5 @@
 use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use regex::Regex;
 
@@ -27,3 +28,11 @@ pub fn is_filtered_out_due_to_invert_regex(filter_regex: &[Regex], dir: &Path) -
         .iter()
         .any(|f| f.is_match(&dir.as_os_str().to_string_lossy()))
 }
+
+// 現在のUNIX時間 [s]
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
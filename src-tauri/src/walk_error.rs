@@ -0,0 +1,103 @@
+use serde::Serialize;
+use std::fmt;
+
+// パラメータの検証エラー
+#[derive(Debug, Clone, Serialize)]
+pub struct ParamError {
+    pub field: String,           // WalkParamsのフィールド名
+    pub index: Option<usize>,    // 配列の場合は要素の番号
+    pub pattern: String,         // 入力された値
+    pub position: Option<usize>, // エラーの位置（文字単位）
+    pub message: String,
+}
+
+impl ParamError {
+    // 正規表現のエラー（regex-syntaxで再度パースしてエラー位置を取得する）
+    pub fn from_regex(field: &str, index: usize, pattern: &str, err: regex::Error) -> Self {
+        let (offset, message) = match regex_syntax::Parser::new().parse(pattern) {
+            Err(regex_syntax::Error::Parse(err)) => {
+                (Some(err.span().start.offset), err.kind().to_string())
+            }
+            Err(regex_syntax::Error::Translate(err)) => {
+                (Some(err.span().start.offset), err.kind().to_string())
+            }
+            // サイズ制限超過など構文以外のエラー
+            _ => (None, err.to_string()),
+        };
+
+        Self {
+            field: field.to_string(),
+            index: Some(index),
+            pattern: pattern.to_string(),
+            position: offset.map(|offset| pattern[..offset].chars().count()),
+            message,
+        }
+    }
+}
+
+impl fmt::Display for ParamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.index {
+            Some(index) => write!(f, "{}[{}] \"{}\"", self.field, index, self.pattern)?,
+            None => write!(f, "{} \"{}\"", self.field, self.pattern)?,
+        }
+        if let Some(position) = self.position {
+            write!(f, " at {}", position)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+// walk_startのエラー（フロントエンドでは"kind"で判別し、"message"を表示する）
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum WalkError {
+    // パラメータのデコードに失敗
+    InvalidJson {
+        message: String,
+    },
+    // パラメータの検証に失敗
+    InvalidParams {
+        message: String,
+        errors: Vec<ParamError>,
+    },
+    // セッションが存在しない
+    SessionNotFound {
+        message: String,
+    },
+    // セッションで既にスキャンを実行中
+    SessionBusy {
+        message: String,
+    },
+    // 結果のエンコードに失敗
+    Encode {
+        message: String,
+    },
+}
+
+impl WalkError {
+    pub fn invalid_params(errors: Vec<ParamError>) -> Self {
+        let message = errors
+            .iter()
+            .map(|err| err.to_string())
+            .collect::<Vec<String>>()
+            .join("; ");
+        WalkError::InvalidParams { message, errors }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            WalkError::InvalidJson { message } => message,
+            WalkError::InvalidParams { message, .. } => message,
+            WalkError::SessionNotFound { message } => message,
+            WalkError::SessionBusy { message } => message,
+            WalkError::Encode { message } => message,
+        }
+    }
+}
+
+impl fmt::Display for WalkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
@@ -1,51 +1,411 @@
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicU64;
 use std::sync::Arc;
 use std::sync::Mutex;
 
-//use crate::node::Node;
+use crate::delete_guard::DeleteGuard;
+use crate::init_walk::WalkParams;
+use crate::init_walk::{rescan_subtree, retain_scanned_paths};
+use crate::node::{AncestorSize, Node, PruneOptions};
+use crate::progress::CancelToken;
 use crate::progress::ErrorHandler;
 use crate::progress::ProgressHandler;
+use crate::progress::ORDERING;
+use crate::snapshot::save_snapshot;
+use crate::snapshot::ScanInfo;
+use crate::tree_watcher::{apply_rescan, plan_rescans, watch_tree};
+use crate::tree_watcher::{TreeChange, TreeWatcher, WatchBatch};
 
-// Walkの結果や実行状態を管理するマネージャー
+// デフォルトのセッションID（session_idを指定しない場合に使用）
+pub const DEFAULT_SESSION_ID: u64 = 0;
+
+// 複数のスキャンセッションを管理するマネージャー
 pub struct WalkManager {
-    //node: Mutex<Option<Node>>,        // ノード格納用
-    errors: Arc<Mutex<ErrorHandler>>, // エラー格納用
-    progress: Arc<ProgressHandler>,   // 処理ステータス格納用
+    sessions: Mutex<HashMap<u64, Arc<ScanSession>>>, // セッション格納用
+    last_session_id: AtomicU64,                      // 最後に発行したセッションID
+    delete_guard: DeleteGuard,                       // 削除操作の保護
+}
+
+// セッションの概要
+#[derive(Serialize)]
+pub struct SessionSummary {
+    pub session_id: u64,
+    pub running: bool,
+    pub watching: bool,
+    pub info: Option<ScanInfo>,
 }
 
 impl WalkManager {
     // 初期化
     pub fn new() -> Self {
+        let mut sessions = HashMap::new();
+        sessions.insert(
+            DEFAULT_SESSION_ID,
+            Arc::new(ScanSession::new(DEFAULT_SESSION_ID)),
+        );
+
         Self {
-            //node: Mutex::new(None),
+            sessions: Mutex::new(sessions),
+            last_session_id: AtomicU64::new(DEFAULT_SESSION_ID),
+            delete_guard: DeleteGuard::new(),
+        }
+    }
+
+    // 新しいセッションを作成
+    pub fn create_session(&self) -> u64 {
+        let session_id = self.last_session_id.fetch_add(1, ORDERING) + 1;
+
+        let mut locked_sessions = self.sessions.lock().unwrap();
+        locked_sessions.insert(session_id, Arc::new(ScanSession::new(session_id)));
+
+        return session_id;
+    }
+
+    // セッションを取得（指定しない場合はデフォルトのセッション）
+    pub fn get_session(&self, session_id: Option<u64>) -> Option<Arc<ScanSession>> {
+        let locked_sessions = self.sessions.lock().unwrap();
+        return locked_sessions
+            .get(&session_id.unwrap_or(DEFAULT_SESSION_ID))
+            .cloned();
+    }
+
+    // セッションを削除（実行中のスキャンは中断する）
+    pub fn close_session(&self, session_id: u64) -> Result<(), String> {
+        if session_id == DEFAULT_SESSION_ID {
+            return Err("The default session cannot be closed".to_string());
+        }
+
+        let mut locked_sessions = self.sessions.lock().unwrap();
+        match locked_sessions.remove(&session_id) {
+            Some(session) => {
+                session.cancel_scan(None);
+                return Ok(());
+            }
+            None => {
+                return Err(format!("No such session: {}", session_id));
+            }
+        }
+    }
+
+    // 削除操作の保護を取得
+    pub fn get_delete_guard(&self) -> &DeleteGuard {
+        return &(self.delete_guard);
+    }
+
+    // セッションの一覧を取得
+    pub fn list_sessions(&self) -> Vec<SessionSummary> {
+        let locked_sessions = self.sessions.lock().unwrap();
+        let mut summaries: Vec<SessionSummary> = locked_sessions
+            .values()
+            .map(|session| SessionSummary {
+                session_id: session.session_id,
+                running: session.is_running(),
+                watching: session.is_watching(),
+                info: session.get_info(),
+            })
+            .collect();
+        summaries.sort_by_key(|summary| summary.session_id);
+        return summaries;
+    }
+}
+
+// 削除したパスをツリーに反映した結果
+#[derive(Serialize)]
+pub struct RemovalResult {
+    pub path: PathBuf,
+    pub freed_size: u64,              // ツリーから差し引いたサイズ [byte]
+    pub ancestors: Vec<AncestorSize>, // 更新後の祖先ノードのサイズ（ルートから順）
+    pub changes: Vec<TreeChange>,     // 祖先を再走査した場合の変更（置き換えられたノード）
+}
+
+// 1つのスキャンの結果や実行状態を管理するセッション
+pub struct ScanSession {
+    session_id: u64,
+    node: Mutex<Option<Node>>,                       // ノード格納用
+    info: Mutex<Option<ScanInfo>>,                   // スキャン情報格納用
+    errors: Arc<Mutex<ErrorHandler>>,                // エラー格納用
+    progress: Arc<ProgressHandler>,                  // 処理ステータス格納用
+    last_scan_id: AtomicU64,                         // 最後に発行したスキャンID
+    current_scan: Mutex<Option<(u64, CancelToken)>>, // 実行中のスキャン
+    watcher: Mutex<Option<TreeWatcher>>,             // ファイルシステムの監視
+}
+
+impl ScanSession {
+    // 初期化
+    fn new(session_id: u64) -> Self {
+        Self {
+            session_id,
+            node: Mutex::new(None),
+            info: Mutex::new(None),
             errors: Arc::new(Mutex::new(ErrorHandler::default())),
-            progress: Arc::new(ProgressHandler::default()),
+            progress: Arc::new(ProgressHandler {
+                session_id,
+                ..ProgressHandler::default()
+            }),
+            last_scan_id: AtomicU64::new(0),
+            current_scan: Mutex::new(None),
+            watcher: Mutex::new(None),
         }
     }
 
-    // 現状使用しないので無効化
-    /*
-    // ノードをセット
+    // セッションIDを取得
+    pub fn get_session_id(&self) -> u64 {
+        return self.session_id;
+    }
+
+    // ノードをセット（監視中の場合は監視を終了する）
     pub fn set_node(&self, node: Option<Node>) {
+        self.stop_watch();
+
         let mut locked_node = self.node.lock().unwrap();
         *locked_node = node;
     }
 
-    // ノード全体を取得
-    pub fn get_node(&self) -> Option<Node> {
+    // 返却するツリーの間引き方（スキャン時のパラメータによる）
+    fn prune_options(&self, root: &Node) -> PruneOptions {
+        let locked_info = self.info.lock().unwrap();
+        return match locked_info.as_ref() {
+            Some(info) => info.params.prune_options(root.size),
+            None => PruneOptions::default(),
+        };
+    }
+
+    // 保持しているノードを参照して処理を行う（ノード全体をクローンしない）
+    pub fn with_node<R>(&self, f: impl FnOnce(Option<&Node>) -> R) -> R {
         let locked_node = self.node.lock().unwrap();
-        return locked_node.clone();
+        return f(locked_node.as_ref());
+    }
+
+    // スキャン情報をセット
+    pub fn set_info(&self, info: Option<ScanInfo>) {
+        let mut locked_info = self.info.lock().unwrap();
+        *locked_info = info;
+    }
+
+    // スキャン情報を取得
+    pub fn get_info(&self) -> Option<ScanInfo> {
+        let locked_info = self.info.lock().unwrap();
+        return locked_info.clone();
+    }
+
+    // スナップショットとして保存
+    pub fn save_snapshot(&self, path: &Path) -> Result<(), String> {
+        let locked_node = self.node.lock().unwrap();
+        let locked_info = self.info.lock().unwrap();
+
+        match (locked_node.as_ref(), locked_info.as_ref()) {
+            (Some(node), Some(info)) => save_snapshot(path, info, node),
+            _ => Err("No scan result to save".to_string()),
+        }
+    }
+
+    // 指定したパス以下のノードを指定した深さまで間引いて取得（pathを指定しない場合はルート）
+    pub fn get_subtree(&self, path: Option<&Path>, depth: Option<usize>) -> Option<Node> {
+        let locked_node = self.node.lock().unwrap();
+        let root = locked_node.as_ref()?;
+        let prune = self.prune_options(root);
+
+        let node = match path {
+            Some(path) => root.find(path)?,
+            None => root,
+        };
+        return Some(node.clone_pruned(depth, &prune));
+    }
+
+    // 指定したパス直下の子ノードを間引いてサイズの降順で取得（子ノードの子は含まず、子ノードの数をnum_childrenに設定する）
+    pub fn get_children(&self, path: &Path, offset: usize, limit: usize) -> Option<Vec<Node>> {
+        let mut children = self.get_subtree(Some(path), Some(1))?.children;
+        children.sort_by_key(|child| Reverse(child.size));
+
+        return Some(children.into_iter().skip(offset).take(limit).collect());
     }
-    */
 
     // errorハンドラを取得
     pub fn get_error_handler(&self) -> &Arc<Mutex<ErrorHandler>> {
         return &(self.errors);
     }
 
-    // 終了フラグを設定
-    pub fn set_abort_flag(&self, flag: bool) {
-        let mut locked_errors = self.errors.lock().unwrap();
-        locked_errors.abort = flag;
+    // スキャンを開始（新しいスキャンIDと中断用トークンを発行、実行中の場合はNone）
+    pub fn begin_scan(&self) -> Option<(u64, CancelToken)> {
+        let mut locked_scan = self.current_scan.lock().unwrap();
+        if locked_scan.is_some() {
+            return None;
+        }
+
+        // スキャン中は監視によるツリーの更新を行わない
+        self.stop_watch();
+
+        let scan_id = self.last_scan_id.fetch_add(1, ORDERING) + 1;
+        let cancel_token = CancelToken::default();
+
+        self.progress.scan_id.store(scan_id, ORDERING);
+
+        *locked_scan = Some((scan_id, cancel_token.clone()));
+
+        return Some((scan_id, cancel_token));
+    }
+
+    // スキャン実行中かどうか
+    pub fn is_running(&self) -> bool {
+        return self.current_scan.lock().unwrap().is_some();
+    }
+
+    // スキャンを終了
+    pub fn end_scan(&self, scan_id: u64) {
+        let mut locked_scan = self.current_scan.lock().unwrap();
+        if locked_scan.as_ref().is_some_and(|(id, _)| *id == scan_id) {
+            *locked_scan = None;
+        }
+    }
+
+    // スキャンを中断（IDを指定した場合は一致する場合のみ、中断した場合はtrue）
+    // IDを指定しないのはセッションを削除する場合のみ
+    pub fn cancel_scan(&self, scan_id: Option<u64>) -> bool {
+        let locked_scan = self.current_scan.lock().unwrap();
+        match locked_scan.as_ref() {
+            Some((id, cancel_token)) if scan_id.is_none() || scan_id == Some(*id) => {
+                cancel_token.cancel();
+                return true;
+            }
+            _ => {
+                return false;
+            }
+        }
+    }
+
+    // スキャンのルートの監視を開始し、ツリーに反映した変更をon_changesに渡す
+    pub fn start_watch(
+        self: &Arc<Self>,
+        on_changes: impl Fn(&[TreeChange]) + Send + 'static,
+    ) -> Result<(), String> {
+        let info = self
+            .get_info()
+            .ok_or_else(|| "No scan result to watch".to_string())?;
+
+        // 監視スレッドがセッションの破棄を妨げないよう弱参照とする
+        let session = Arc::downgrade(self);
+        let watcher = watch_tree(&info.root, move |batch| {
+            if let Some(session) = session.upgrade() {
+                let changes = session.apply_watch_batch(&info.params, &batch);
+                if !changes.is_empty() {
+                    on_changes(&changes);
+                }
+            }
+        })?;
+
+        *self.watcher.lock().unwrap() = Some(watcher);
+
+        return Ok(());
+    }
+
+    // 監視を終了
+    pub fn stop_watch(&self) {
+        *self.watcher.lock().unwrap() = None;
+    }
+
+    // 監視中かどうか
+    pub fn is_watching(&self) -> bool {
+        return self.watcher.lock().unwrap().is_some();
+    }
+
+    // 削除したパスをツリーから取り除き、祖先ノードのサイズから差し引く
+    // 間引かれていてツリーに無いパスの場合や"N small items"ノードにまとめられている場合は、
+    // ツリーに存在する最も深い祖先を再走査する
+    pub fn remove_path(&self, path: &Path) -> RemovalResult {
+        let mut result = RemovalResult {
+            path: path.to_path_buf(),
+            freed_size: 0,
+            ancestors: vec![],
+            changes: vec![],
+        };
+
+        {
+            let mut locked_node = self.node.lock().unwrap();
+            let Some(node) = locked_node.as_mut() else {
+                return result;
+            };
+
+            if let Some(removed) = node.remove_descendant(path) {
+                result.freed_size = removed.size;
+                result.ancestors = node.ancestor_sizes(path);
+                return result;
+            }
+        }
+
+        let Some(info) = self.get_info() else {
+            return result;
+        };
+
+        let batch = WatchBatch {
+            paths: vec![path.to_path_buf()],
+            overflow: false,
+        };
+        let root_size = || self.with_node(|node| node.map_or(0, |node| node.size));
+
+        let before = root_size();
+        result.changes = self.apply_watch_batch(&info.params, &batch);
+        result.freed_size = before.saturating_sub(root_size());
+        result.ancestors = self.with_node(|node| {
+            node.map(|node| node.ancestor_sizes(path))
+                .unwrap_or_default()
+        });
+
+        return result;
+    }
+
+    // パスのサイズ（間引かれていてツリーに無い場合はスキャン時と同じパラメータで走査する、ツリー外の場合は0）
+    pub fn measure_path(&self, path: &Path) -> u64 {
+        let found = self.with_node(|node| {
+            let anchor = node?.find_deepest(path)?;
+            if anchor.name == path {
+                return Some((anchor.size, anchor.depth, true));
+            }
+            let depth = anchor.depth + path.strip_prefix(&anchor.name).ok()?.components().count();
+            return Some((0, depth, false));
+        });
+
+        match (found, self.get_info()) {
+            (Some((size, _, true)), _) => size,
+            (Some((_, depth, false)), Some(info)) => {
+                rescan_subtree(&info.params, path, depth).map_or(0, |node| node.size)
+            }
+            _ => 0,
+        }
+    }
+
+    // 監視で検出した変更を再走査してツリーに反映（再走査中はノードをロックしない）
+    // 除外されたパスの変更はツリーに影響しないため、再走査の対象としない
+    fn apply_watch_batch(&self, params: &WalkParams, batch: &WatchBatch) -> Vec<TreeChange> {
+        let batch = WatchBatch {
+            paths: retain_scanned_paths(params, batch.paths.clone()),
+            overflow: batch.overflow,
+        };
+
+        let targets = self.with_node(|node| match node {
+            Some(node) => plan_rescans(node, &batch),
+            None => vec![],
+        });
+
+        let rescanned: Vec<(PathBuf, Option<Node>)> = targets
+            .into_iter()
+            .map(|(path, depth)| {
+                let node = rescan_subtree(params, &path, depth);
+                (path, node)
+            })
+            .collect();
+
+        let mut locked_node = self.node.lock().unwrap();
+        let Some(node) = locked_node.as_mut() else {
+            return vec![];
+        };
+
+        return rescanned
+            .into_iter()
+            .filter_map(|(path, rescanned)| apply_rescan(node, &path, rescanned))
+            .collect();
     }
 
     // progressハンドラを取得
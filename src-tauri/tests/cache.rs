@@ -0,0 +1,96 @@
+// スキャンキャッシュの再利用のテスト
+// ディレクトリの更新日時を変更するためUnixのみ
+#![cfg(unix)]
+
+mod common;
+
+use std::fs::File;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use common::{params, scan, size_of, write_file};
+use tauri_app_lib::WalkParams;
+use tempfile::TempDir;
+
+// 直後に更新されたディレクトリはキャッシュされないため、更新日時を過去にする
+fn set_past_mtime(dirs: &[&Path]) {
+    let past = SystemTime::now() - Duration::from_secs(60);
+    for dir in dirs {
+        File::open(dir).unwrap().set_modified(past).unwrap();
+    }
+}
+
+// root/a/f (1000)
+fn cached_tree() -> (TempDir, TempDir) {
+    let tmp = TempDir::new().unwrap();
+    let cache_dir = TempDir::new().unwrap();
+    write_file(&tmp.path().join("a/f"), 1_000);
+    set_past_mtime(&[tmp.path(), &tmp.path().join("a")]);
+    return (tmp, cache_dir);
+}
+
+fn with_cache(root: &Path, cache_dir: &Path) -> WalkParams {
+    WalkParams {
+        use_apparent_size: true,
+        cache_file: Some(
+            cache_dir
+                .join("cache.json.gz")
+                .to_string_lossy()
+                .into_owned(),
+        ),
+        ..params(root)
+    }
+}
+
+#[test]
+fn unchanged_directories_are_reused() {
+    let (tmp, cache_dir) = cached_tree();
+    let root = tmp.path();
+
+    scan(with_cache(root, cache_dir.path()));
+    assert!(cache_dir.path().join("cache.json.gz").exists());
+
+    // ファイルの内容のみを変更してもディレクトリの更新日時は変わらない
+    write_file(&root.join("a/f"), 5_000);
+
+    let cached = scan(with_cache(root, cache_dir.path())).node;
+    assert_eq!(size_of(&cached, &root.join("a/f")), Some(1_000));
+
+    let fresh = scan(WalkParams {
+        cache_file: None,
+        ..with_cache(root, cache_dir.path())
+    })
+    .node;
+    assert_eq!(size_of(&fresh, &root.join("a/f")), Some(5_000));
+}
+
+#[test]
+fn modified_directories_are_rescanned() {
+    let (tmp, cache_dir) = cached_tree();
+    let root = tmp.path();
+
+    scan(with_cache(root, cache_dir.path()));
+
+    write_file(&root.join("a/g"), 2_000);
+
+    let node = scan(with_cache(root, cache_dir.path())).node;
+    assert_eq!(size_of(&node, &root.join("a/f")), Some(1_000));
+    assert_eq!(size_of(&node, &root.join("a/g")), Some(2_000));
+}
+
+#[test]
+fn cache_is_ignored_when_parameters_differ() {
+    let (tmp, cache_dir) = cached_tree();
+    let root = tmp.path();
+
+    scan(with_cache(root, cache_dir.path()));
+
+    write_file(&root.join("a/f"), 5_000);
+
+    let node = scan(WalkParams {
+        regex_invert_filter: Some(vec!["never-matches".to_string()]),
+        ..with_cache(root, cache_dir.path())
+    })
+    .node;
+    assert_eq!(size_of(&node, &root.join("a/f")), Some(5_000));
+}
@@ -0,0 +1,90 @@
+// 統合テストの共通処理（一時ディレクトリの作成とスキャンの実行）
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use tauri_app_lib::{
+    init_walk, CancelToken, ErrorHandler, Node, ProgressHandler, ScanObserver, WalkParams,
+};
+
+// 途中結果を記録するオブザーバー
+#[derive(Default)]
+pub struct TestObserver {
+    pub subtrees: Mutex<Vec<Node>>,
+}
+
+impl ScanObserver for TestObserver {
+    fn on_progress(&self, _progress: &ProgressHandler) {}
+
+    fn on_subtrees(&self, subtrees: &[Node]) {
+        self.subtrees.lock().unwrap().extend_from_slice(subtrees);
+    }
+
+    // エラーは戻り値で確認する
+    fn on_errors(&self, _errors: &ErrorHandler) {}
+}
+
+// スキャンの結果
+pub struct ScanResult {
+    pub node: Node,
+    pub errors: ErrorHandler,
+    pub subtrees: Vec<Node>,
+}
+
+// rootを対象とするパラメータ
+pub fn params(root: &Path) -> WalkParams {
+    WalkParams {
+        target_directory: root.to_string_lossy().into_owned(),
+        ..WalkParams::default()
+    }
+}
+
+// スキャンを実行（失敗した場合はパニック）
+pub fn scan(params: WalkParams) -> ScanResult {
+    let errors = Arc::new(Mutex::new(ErrorHandler::default()));
+    let observer = Arc::new(TestObserver::default());
+
+    let node = init_walk(
+        params,
+        &errors,
+        &Arc::new(ProgressHandler::default()),
+        &CancelToken::default(),
+        observer.clone(),
+    )
+    .expect("invalid parameters")
+    .expect("no scan result");
+
+    let errors = std::mem::take(&mut *errors.lock().unwrap());
+    let subtrees = std::mem::take(&mut *observer.subtrees.lock().unwrap());
+    return ScanResult {
+        node,
+        errors,
+        subtrees,
+    };
+}
+
+// 指定したサイズのファイルを作成（親ディレクトリも作成する）
+pub fn write_file(path: &Path, size: usize) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, vec![b'x'; size]).unwrap();
+}
+
+// パスのノードのサイズ（ツリーに無い場合はNone）
+pub fn size_of(node: &Node, path: &Path) -> Option<u64> {
+    node.find(path).map(|node| node.size)
+}
+
+// 子ノードのサイズの合計が各ディレクトリのサイズ以下であることを確認
+pub fn assert_consistent(node: &Node) {
+    let children_size: u64 = node.children.iter().map(|child| child.size).sum();
+    assert!(
+        children_size <= node.size,
+        "children of {} are larger than the node",
+        node.name.display()
+    );
+    for child in node.children.iter() {
+        assert_consistent(child);
+    }
+}
@@ -0,0 +1,195 @@
+// 結果のツリーの間引きと途中結果の通知のテスト
+mod common;
+
+use std::path::Path;
+
+use common::{assert_consistent, params, scan, size_of, write_file};
+use tauri_app_lib::{top_n, type_breakdown, Node, TopNFilter, WalkParams};
+use tempfile::TempDir;
+
+// root
+// ├── big/f (100000)
+// ├── big/sub/g (50000)
+// ├── s1.txt (100)
+// ├── s2.txt (200)
+// └── s3.txt (300)
+fn small_and_large_items() -> TempDir {
+    let tmp = TempDir::new().unwrap();
+    write_file(&tmp.path().join("big/f"), 100_000);
+    write_file(&tmp.path().join("big/sub/g"), 50_000);
+    write_file(&tmp.path().join("s1.txt"), 100);
+    write_file(&tmp.path().join("s2.txt"), 200);
+    write_file(&tmp.path().join("s3.txt"), 300);
+    return tmp;
+}
+
+fn pruned(root: &Path) -> WalkParams {
+    WalkParams {
+        use_apparent_size: true,
+        min_size: Some(1_000),
+        max_depth: Some(1),
+        ..params(root)
+    }
+}
+
+fn collapsed_children(node: &Node) -> Vec<&Node> {
+    node.children
+        .iter()
+        .filter(|child| child.is_collapsed())
+        .collect()
+}
+
+#[test]
+fn scan_result_is_not_pruned() {
+    let tmp = small_and_large_items();
+    let root = tmp.path();
+
+    let node = scan(pruned(root)).node;
+
+    // 間引きは返却する複製にのみ適用する
+    assert_eq!(size_of(&node, &root.join("s1.txt")), Some(100));
+    assert_eq!(size_of(&node, &root.join("big/sub/g")), Some(50_000));
+    assert!(collapsed_children(&node).is_empty());
+}
+
+#[test]
+fn pruned_copy_collapses_small_items_and_drops_deep_nodes() {
+    let tmp = small_and_large_items();
+    let root = tmp.path();
+
+    let params = pruned(root);
+    let node = scan(params.clone()).node;
+    let copy = node.clone_pruned(None, &params.prune_options(node.size));
+
+    let collapsed = collapsed_children(&copy);
+    assert_eq!(collapsed.len(), 1);
+    assert_eq!(collapsed[0].collapsed_items, Some(3));
+    assert_eq!(collapsed[0].size, 600);
+    assert!(copy.find(&root.join("s1.txt")).is_none());
+
+    // max_depthより深いノードは含めないがサイズは変わらない
+    let big = copy.find(&root.join("big")).unwrap();
+    assert!(big.children.is_empty());
+    assert_eq!(Some(big.size), size_of(&node, &root.join("big")));
+    assert_eq!(copy.size, node.size);
+    assert_consistent(&copy);
+}
+
+#[test]
+fn min_percentage_is_relative_to_the_root() {
+    let tmp = small_and_large_items();
+    let root = tmp.path();
+
+    let params = WalkParams {
+        use_apparent_size: true,
+        min_percentage: Some(1.0),
+        ..params(root)
+    };
+    let node = scan(params.clone()).node;
+
+    assert!(params.prune_options(node.size).threshold > 300);
+    let copy = node.clone_pruned(None, &params.prune_options(node.size));
+    assert_eq!(collapsed_children(&copy).len(), 1);
+}
+
+#[test]
+fn collapsed_node_is_not_found_by_path() {
+    let tmp = small_and_large_items();
+    let root = tmp.path();
+    // 合成ノードと同じ名前の実在するファイル
+    write_file(&root.join("big/2 small items"), 20_000);
+    write_file(&root.join("big/t1"), 10);
+    write_file(&root.join("big/t2"), 10);
+
+    let params = WalkParams {
+        max_depth: None,
+        ..pruned(root)
+    };
+    let node = scan(params.clone()).node;
+    let copy = node.clone_pruned(None, &params.prune_options(node.size));
+
+    let real = copy.find(&root.join("big/2 small items")).unwrap();
+    assert!(!real.is_collapsed());
+    assert_eq!(real.size, 20_000);
+}
+
+#[test]
+fn reports_skip_collapsed_nodes() {
+    let tmp = small_and_large_items();
+    let root = tmp.path();
+
+    let params = pruned(root);
+    let node = scan(params.clone()).node;
+    let copy = node.clone_pruned(None, &params.prune_options(node.size));
+
+    let report = top_n(&copy, 10, &TopNFilter::default());
+    assert!(report
+        .files
+        .iter()
+        .chain(report.directories.iter())
+        .all(|entry| copy.find(&entry.name).is_some()));
+
+    // 種類別の内訳は合成ノードを集計しない
+    let txt = |node: &Node| {
+        type_breakdown(node)
+            .by_extension
+            .into_iter()
+            .find(|group| group.name == "txt")
+            .map(|group| (group.size, group.count))
+    };
+    assert_eq!(txt(&node), Some((600, 3)));
+    assert_eq!(txt(&copy), None);
+}
+
+#[test]
+fn top_n_uses_the_full_tree_regardless_of_max_depth() {
+    let tmp = small_and_large_items();
+    let root = tmp.path();
+
+    let node = scan(WalkParams {
+        max_depth: Some(0),
+        ..pruned(root)
+    })
+    .node;
+
+    let report = top_n(&node, 2, &TopNFilter::default());
+    assert_eq!(report.files[0].name, root.join("big/f"));
+    assert_eq!(report.files[0].size, 100_000);
+    assert_eq!(report.files[1].name, root.join("big/sub/g"));
+
+    // 直下のファイルのみのサイズはサブディレクトリを除く
+    let big_own = report
+        .directories_own
+        .iter()
+        .find(|entry| entry.name == root.join("big"))
+        .unwrap();
+    let big = size_of(&node, &root.join("big")).unwrap();
+    let sub = size_of(&node, &root.join("big/sub")).unwrap();
+    assert_eq!(big_own.size, big - sub);
+}
+
+#[test]
+fn streamed_subtrees_match_the_final_tree() {
+    let tmp = small_and_large_items();
+    let root = tmp.path();
+    write_file(&root.join("other/h"), 5_000);
+
+    let result = scan(WalkParams {
+        stream_depth: Some(1),
+        ..pruned(root)
+    });
+
+    let mut names: Vec<&Path> = result
+        .subtrees
+        .iter()
+        .map(|subtree| subtree.name.as_path())
+        .collect();
+    names.sort();
+    assert_eq!(names, vec![root.join("big"), root.join("other")]);
+
+    for subtree in result.subtrees.iter() {
+        assert_eq!(Some(subtree.size), size_of(&result.node, &subtree.name));
+        // 途中結果もmax_depthで間引く
+        assert!(subtree.children.is_empty());
+    }
+}
@@ -0,0 +1,130 @@
+// 差分と重複ファイルの検出のテスト
+mod common;
+
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use common::{params, scan, write_file, TestObserver};
+use tauri_app_lib::{diff_nodes, duplicate_candidates, find_duplicates};
+use tauri_app_lib::{DiffNode, DiffStatus, ProgressHandler, WalkParams};
+use tempfile::TempDir;
+
+fn status_of(diff: &DiffNode, name: &Path) -> Option<DiffStatus> {
+    if diff.name == name {
+        return Some(diff.status);
+    }
+    return diff
+        .children
+        .iter()
+        .find_map(|child| status_of(child, name));
+}
+
+#[test]
+fn diff_reports_added_removed_and_changed_nodes() {
+    let tmp = TempDir::new().unwrap();
+    let root = tmp.path();
+    write_file(&root.join("a/kept"), 1_000);
+    write_file(&root.join("a/grown"), 1_000);
+    write_file(&root.join("b/removed"), 1_000);
+
+    let apparent = WalkParams {
+        use_apparent_size: true,
+        ..params(root)
+    };
+    let old = scan(apparent.clone()).node;
+
+    write_file(&root.join("a/grown"), 5_000);
+    fs::remove_file(root.join("b/removed")).unwrap();
+    write_file(&root.join("c/added"), 2_000);
+    let new = scan(apparent).node;
+
+    let diff = diff_nodes(&old, &new, None).unwrap();
+    assert_eq!(diff.delta, new.size as i64 - old.size as i64);
+    assert_eq!(
+        status_of(&diff, &root.join("a/kept")),
+        Some(DiffStatus::Unchanged)
+    );
+    assert_eq!(
+        status_of(&diff, &root.join("a/grown")),
+        Some(DiffStatus::Changed)
+    );
+    assert_eq!(
+        status_of(&diff, &root.join("b/removed")),
+        Some(DiffStatus::Removed)
+    );
+    assert_eq!(status_of(&diff, &root.join("c")), Some(DiffStatus::Added));
+
+    // 変化量の絶対値の降順
+    let deltas: Vec<u64> = diff
+        .children
+        .iter()
+        .map(|child| child.delta.unsigned_abs())
+        .collect();
+    assert!(deltas.windows(2).all(|pair| pair[0] >= pair[1]));
+
+    // max_depthより深いノードは含めない
+    let shallow = diff_nodes(&old, &new, Some(1)).unwrap();
+    assert!(shallow
+        .children
+        .iter()
+        .all(|child| child.children.is_empty()));
+
+    let other = scan(params(&root.join("a"))).node;
+    assert!(diff_nodes(&old, &other, None).is_err());
+}
+
+#[test]
+fn duplicates_are_grouped_by_content() {
+    let tmp = TempDir::new().unwrap();
+    let root = tmp.path();
+    fs::create_dir_all(root.join("a")).unwrap();
+    fs::create_dir_all(root.join("b")).unwrap();
+    fs::write(root.join("a/one"), vec![1u8; 10_000]).unwrap();
+    fs::write(root.join("b/one"), vec![1u8; 10_000]).unwrap();
+    // サイズは同じだが内容が異なる
+    fs::write(root.join("b/two"), vec![2u8; 10_000]).unwrap();
+    fs::write(root.join("unique"), vec![1u8; 3_000]).unwrap();
+
+    let node = scan(WalkParams {
+        use_apparent_size: true,
+        ..params(root)
+    })
+    .node;
+
+    let mut candidates = duplicate_candidates(&node, 1);
+    candidates.sort();
+    assert_eq!(
+        candidates,
+        vec![root.join("a/one"), root.join("b/one"), root.join("b/two")]
+    );
+
+    let report = find_duplicates(
+        candidates,
+        &Arc::new(ProgressHandler::default()),
+        Arc::new(TestObserver::default()),
+    );
+    assert_eq!(report.groups.len(), 1);
+    assert_eq!(
+        report.groups[0].paths,
+        vec![root.join("a/one"), root.join("b/one")]
+    );
+    assert_eq!(report.groups[0].reclaimable, 10_000);
+    assert_eq!(report.total_reclaimable, 10_000);
+}
+
+#[cfg(unix)]
+#[test]
+fn hard_links_are_not_duplicates() {
+    let tmp = TempDir::new().unwrap();
+    let root = tmp.path();
+    fs::write(root.join("one"), vec![1u8; 10_000]).unwrap();
+    fs::hard_link(root.join("one"), root.join("link")).unwrap();
+
+    let report = find_duplicates(
+        vec![root.join("one"), root.join("link")],
+        &Arc::new(ProgressHandler::default()),
+        Arc::new(TestObserver::default()),
+    );
+    assert!(report.groups.is_empty());
+}
@@ -0,0 +1,84 @@
+// シンボリックリンクを辿るスキャンのテスト
+#![cfg(unix)]
+
+mod common;
+
+use std::os::unix::fs::symlink;
+use std::path::Path;
+
+use common::{params, scan, size_of, write_file};
+use tauri_app_lib::WalkParams;
+use tempfile::TempDir;
+
+// root
+// ├── link -> z/deep/a（実際のパスより浅いため先に到達する）
+// └── z/deep/a/x/f
+fn link_to_deeper_directory() -> TempDir {
+    let tmp = TempDir::new().unwrap();
+    write_file(&tmp.path().join("z/deep/a/x/f"), 50_000);
+    symlink("z/deep/a", tmp.path().join("link")).unwrap();
+    return tmp;
+}
+
+fn follow(root: &Path, use_apparent_size: bool) -> WalkParams {
+    WalkParams {
+        follow_symlinks: true,
+        use_apparent_size,
+        ..params(root)
+    }
+}
+
+#[test]
+fn real_path_is_kept_when_also_reached_through_a_link() {
+    for use_apparent_size in [false, true] {
+        let tmp = link_to_deeper_directory();
+        let root = tmp.path();
+
+        let node = scan(follow(root, use_apparent_size)).node;
+        let unfollowed = scan(WalkParams {
+            use_apparent_size,
+            ..params(root)
+        })
+        .node;
+
+        // 実際のパスに集計され、リンクは辿らない場合と同じくファイルとして残る
+        assert!(size_of(&node, &root.join("z/deep/a/x/f")).is_some());
+        assert_eq!(size_of(&node, &root.join("link/x/f")), None);
+        let link = node.find(&root.join("link")).unwrap();
+        assert!(link.children.is_empty());
+        assert!(!link.is_directory());
+
+        // 同じ内容を二重に集計しない
+        assert_eq!(node.size, unfollowed.size);
+    }
+}
+
+#[test]
+fn link_to_parent_does_not_loop() {
+    let tmp = TempDir::new().unwrap();
+    let root = tmp.path();
+    write_file(&root.join("a/f"), 1_000);
+    symlink("..", root.join("a/up")).unwrap();
+
+    let node = scan(follow(root, true)).node;
+
+    assert!(size_of(&node, &root.join("a/f")).is_some());
+    assert!(node.find(&root.join("a/up")).unwrap().children.is_empty());
+}
+
+#[test]
+fn link_outside_of_root_is_followed() {
+    let outside = TempDir::new().unwrap();
+    write_file(&outside.path().join("data/f"), 20_000);
+
+    let tmp = TempDir::new().unwrap();
+    let root = tmp.path();
+    symlink(outside.path().join("data"), root.join("data")).unwrap();
+
+    let node = scan(follow(root, true)).node;
+
+    let linked = node.find(&root.join("data")).unwrap();
+    assert!(linked.via_symlink);
+    assert_eq!(size_of(&node, &root.join("data/f")), Some(20_000));
+    assert!(node.find(&root.join("data/f")).unwrap().via_symlink);
+}
@@ -0,0 +1,185 @@
+// 走査とフィルターのテスト
+mod common;
+
+use std::fs;
+use std::sync::{Arc, Mutex};
+
+use common::{assert_consistent, params, scan, size_of, write_file};
+use tauri_app_lib::{init_walk, validate_walk_params, WalkError, WalkParams};
+use tauri_app_lib::{CancelToken, ErrorHandler, ProgressHandler};
+use tempfile::TempDir;
+
+// root
+// ├── a/f1 (1000)
+// ├── a/b/f2 (2000)
+// ├── c/f3.log (3000)
+// └── f4.txt (4000)
+fn sample_tree() -> TempDir {
+    let tmp = TempDir::new().unwrap();
+    write_file(&tmp.path().join("a/f1"), 1_000);
+    write_file(&tmp.path().join("a/b/f2"), 2_000);
+    write_file(&tmp.path().join("c/f3.log"), 3_000);
+    write_file(&tmp.path().join("f4.txt"), 4_000);
+    return tmp;
+}
+
+fn apparent(params: WalkParams) -> WalkParams {
+    WalkParams {
+        use_apparent_size: true,
+        ..params
+    }
+}
+
+#[test]
+fn apparent_sizes_are_summed_up_to_the_root() {
+    let tmp = sample_tree();
+    let root = tmp.path();
+
+    let result = scan(apparent(params(root)));
+    let node = result.node;
+
+    assert_eq!(size_of(&node, &root.join("a/f1")), Some(1_000));
+    assert_eq!(size_of(&node, &root.join("a/b/f2")), Some(2_000));
+    assert_eq!(size_of(&node, &root.join("c/f3.log")), Some(3_000));
+    assert_eq!(size_of(&node, &root.join("f4.txt")), Some(4_000));
+    assert!(size_of(&node, &root.join("a")).unwrap() >= 3_000);
+    assert!(node.size >= 10_000);
+    assert!(node.find(&root.join("a/b")).unwrap().is_directory());
+    assert!(result.errors.errors.is_empty());
+    assert_consistent(&node);
+}
+
+#[test]
+fn disk_usage_is_consistent() {
+    let tmp = sample_tree();
+    let node = scan(params(tmp.path())).node;
+
+    assert_eq!(node.children.len(), 3);
+    assert_consistent(&node);
+}
+
+#[test]
+fn regex_filters_select_files() {
+    let tmp = sample_tree();
+    let root = tmp.path();
+
+    let node = scan(apparent(WalkParams {
+        regex_filter: Some(vec![r"\.log$".to_string()]),
+        ..params(root)
+    }))
+    .node;
+    assert_eq!(size_of(&node, &root.join("c/f3.log")), Some(3_000));
+    assert_eq!(size_of(&node, &root.join("a/f1")), None);
+    assert_eq!(size_of(&node, &root.join("f4.txt")), None);
+
+    let node = scan(apparent(WalkParams {
+        regex_invert_filter: Some(vec![r"\.log$".to_string()]),
+        ..params(root)
+    }))
+    .node;
+    assert_eq!(size_of(&node, &root.join("c/f3.log")), None);
+    assert_eq!(size_of(&node, &root.join("a/f1")), Some(1_000));
+}
+
+#[test]
+fn ignored_directories_are_not_scanned() {
+    let tmp = sample_tree();
+    let root = tmp.path();
+
+    let node = scan(WalkParams {
+        ignore_directories: Some(vec!["a".to_string()]),
+        ..params(root)
+    })
+    .node;
+
+    assert!(node.find(&root.join("a")).is_none());
+    assert!(node.find(&root.join("c/f3.log")).is_some());
+}
+
+#[test]
+fn glob_patterns_exclude_and_include_files() {
+    let tmp = sample_tree();
+    let root = tmp.path();
+
+    let node = scan(WalkParams {
+        exclude_patterns: Some(vec!["b/".to_string(), "*.log".to_string()]),
+        ..params(root)
+    })
+    .node;
+    assert!(node.find(&root.join("a/b")).is_none());
+    assert!(node.find(&root.join("c/f3.log")).is_none());
+    assert!(node.find(&root.join("a/f1")).is_some());
+
+    let node = scan(WalkParams {
+        include_patterns: Some(vec!["*.txt".to_string()]),
+        ..params(root)
+    })
+    .node;
+    assert!(node.find(&root.join("f4.txt")).is_some());
+    assert!(node.find(&root.join("a/f1")).is_none());
+    assert!(node.find(&root.join("c/f3.log")).is_none());
+}
+
+#[test]
+fn ignore_files_are_applied_when_enabled() {
+    let tmp = sample_tree();
+    let root = tmp.path();
+    fs::write(root.join("a/.gitignore"), "b/\n").unwrap();
+
+    let node = scan(WalkParams {
+        use_ignore_files: true,
+        ..params(root)
+    })
+    .node;
+    assert!(node.find(&root.join("a/b")).is_none());
+    assert!(node.find(&root.join("a/f1")).is_some());
+
+    let node = scan(params(root)).node;
+    assert!(node.find(&root.join("a/b")).is_some());
+}
+
+#[test]
+fn invalid_patterns_are_reported_with_their_index() {
+    let tmp = sample_tree();
+
+    let params = WalkParams {
+        regex_filter: Some(vec!["ok".to_string(), "a(".to_string()]),
+        exclude_patterns: Some(vec!["*.o".to_string(), "[".to_string()]),
+        ..params(tmp.path())
+    };
+
+    let errors = validate_walk_params(&params);
+    assert_eq!(errors.len(), 2);
+    assert!(errors
+        .iter()
+        .any(|err| err.field == "regex_filter" && err.index == Some(1) && err.position.is_some()));
+    assert!(errors
+        .iter()
+        .any(|err| err.field == "exclude_patterns" && err.index == Some(1)));
+
+    let result = init_walk(
+        params,
+        &Arc::new(Mutex::new(ErrorHandler::default())),
+        &Arc::new(ProgressHandler::default()),
+        &CancelToken::default(),
+        Arc::new(common::TestObserver::default()),
+    );
+    assert!(matches!(result, Err(WalkError::InvalidParams { .. })));
+}
+
+#[test]
+fn cancelled_scan_returns_no_result() {
+    let tmp = sample_tree();
+
+    let cancel_token = CancelToken::default();
+    cancel_token.cancel();
+    let result = init_walk(
+        params(tmp.path()),
+        &Arc::new(Mutex::new(ErrorHandler::default())),
+        &Arc::new(ProgressHandler::default()),
+        &cancel_token,
+        Arc::new(common::TestObserver::default()),
+    );
+
+    assert!(matches!(result, Ok(None)));
+}
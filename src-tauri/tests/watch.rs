@@ -0,0 +1,118 @@
+// ファイル監視による更新の計画のテスト
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+
+use common::{params, scan, write_file};
+use tauri_app_lib::{apply_rescan, plan_rescans, rescan_subtree, retain_scanned_paths};
+use tauri_app_lib::{WalkParams, WatchBatch};
+use tempfile::TempDir;
+
+#[test]
+fn changes_under_excluded_paths_are_dropped() {
+    let tmp = TempDir::new().unwrap();
+    let root = tmp.path();
+    write_file(&root.join("src/main.rs"), 100);
+    write_file(&root.join("target/debug/app"), 100);
+    write_file(&root.join("ignored/f"), 100);
+    write_file(&root.join("sub/build/out.o"), 100);
+    fs::write(root.join("sub/.gitignore"), "build/\n").unwrap();
+
+    let params = WalkParams {
+        exclude_patterns: Some(vec!["target/".to_string()]),
+        ignore_directories: Some(vec!["ignored".to_string()]),
+        use_ignore_files: true,
+        ..params(root)
+    };
+
+    let changed: Vec<PathBuf> = [
+        "target/debug/app",
+        "target/debug/new",
+        "ignored/f",
+        "sub/build/out.o",
+        "src/main.rs",
+        "src/new.rs",
+    ]
+    .iter()
+    .map(|path| root.join(path))
+    .collect();
+
+    assert_eq!(
+        retain_scanned_paths(&params, changed),
+        vec![root.join("src/main.rs"), root.join("src/new.rs")]
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn changes_through_unfollowed_links_are_dropped() {
+    let tmp = TempDir::new().unwrap();
+    let root = tmp.path();
+    write_file(&root.join("a/f"), 100);
+    std::os::unix::fs::symlink("a", root.join("link")).unwrap();
+
+    let changed = vec![root.join("link/f"), root.join("link")];
+
+    assert_eq!(
+        retain_scanned_paths(&params(root), changed.clone()),
+        vec![root.join("link")]
+    );
+
+    let follow = WalkParams {
+        follow_symlinks: true,
+        ..params(root)
+    };
+    assert_eq!(retain_scanned_paths(&follow, changed.clone()), changed);
+}
+
+#[test]
+fn excluded_change_does_not_rescan_the_root() {
+    let tmp = TempDir::new().unwrap();
+    let root = tmp.path();
+    write_file(&root.join("src/main.rs"), 100);
+    write_file(&root.join("target/debug/app"), 100);
+
+    let params = WalkParams {
+        exclude_patterns: Some(vec!["target/".to_string()]),
+        ..params(root)
+    };
+    let node = scan(params.clone()).node;
+
+    let batch = WatchBatch {
+        paths: retain_scanned_paths(&params, vec![root.join("target/debug/app")]),
+        overflow: false,
+    };
+    assert!(plan_rescans(&node, &batch).is_empty());
+}
+
+#[test]
+fn rescanned_change_matches_a_fresh_scan() {
+    let tmp = TempDir::new().unwrap();
+    let root = tmp.path();
+    write_file(&root.join("a/b/f1"), 10_000);
+    write_file(&root.join("c/f2"), 10_000);
+
+    let params = params(root);
+    let mut node = scan(params.clone()).node;
+
+    write_file(&root.join("a/b/f3"), 30_000);
+    fs::remove_file(root.join("c/f2")).unwrap();
+
+    let batch = WatchBatch {
+        paths: retain_scanned_paths(&params, vec![root.join("a/b/f3"), root.join("c/f2")]),
+        overflow: false,
+    };
+    for (path, depth) in plan_rescans(&node, &batch) {
+        let rescanned = rescan_subtree(&params, &path, depth);
+        apply_rescan(&mut node, &path, rescanned);
+    }
+
+    let fresh = scan(params).node;
+    assert_eq!(node.size, fresh.size);
+    assert_eq!(
+        node.find(&root.join("a")).map(|n| n.size),
+        fresh.find(&root.join("a")).map(|n| n.size)
+    );
+    assert!(node.find(&root.join("c/f2")).is_none());
+}
@@ -66,15 +66,21 @@ async function _removeFileOrDirectory(path, node, onRemoveFileOrDirectory) {
         dialogMessage = i18n.global.t('removal_alert.file_desc') + '\n\n\n' + path + '\n';
     }
 
+    // 削除できるか確認して確認用トークンを取得
+    const token = await prepareDelete(path);
+    if (!token) {
+        return;
+    }
+
     const result = await ask(dialogMessage, dialogTitle);
     // YESの場合
     if (result) {
         // バックエンド側の関数を実行
-        await invoke('remove_file_or_directory', { path: path })
+        await invoke('remove_file_or_directory', { path: path, token: token })
             // 成功した場合
-            .then((_success) => {
-                // Nodeを削除
-                onRemoveFileOrDirectory(node);
+            .then((success) => {
+                // Nodeを削除（解放したサイズと更新後の祖先ノードのサイズを反映する）
+                onRemoveFileOrDirectory(node, JSON.parse(success));
             })
             // 失敗した場合
             .catch((failure) => {
@@ -97,15 +103,21 @@ async function moveToTrash(path, node, onMoveToTrash) {
         dialogMessage = i18n.global.t('removal_alert.file_desc') + '\n\n\n' + path + '\n';
     }
 
+    // 削除できるか確認して確認用トークンを取得
+    const token = await prepareDelete(path);
+    if (!token) {
+        return;
+    }
+
     const result = await ask(dialogMessage, dialogTitle);
     // YESの場合
     if (result) {
         // バックエンド側の関数を実行
-        await invoke('move_to_trash', { path: path })
+        await invoke('move_to_trash', { path: path, token: token })
             // 成功した場合
-            .then((_success) => {
-                // Nodeを削除
-                onMoveToTrash(node);
+            .then((success) => {
+                // Nodeを削除（解放したサイズと更新後の祖先ノードのサイズを反映する）
+                onMoveToTrash(node, JSON.parse(success));
             })
             // 失敗した場合
             .catch((failure) => {
@@ -115,5 +127,26 @@ async function moveToTrash(path, node, onMoveToTrash) {
     }
 }
 
+// 削除前の確認を行う関数（削除できない場合はメッセージを表示してnullを返す）
+async function prepareDelete(path) {
+    return await invoke('prepare_delete', { paths: [path] })
+        // 成功した場合
+        .then((success) => {
+            const preparation = JSON.parse(success);
+            if (!preparation.token) {
+                // Message Dialog
+                message(preparation.items.map((item) => item.error).join('\n'));
+                return null;
+            }
+            return preparation.token;
+        })
+        // 失敗した場合
+        .catch((failure) => {
+            // Message Dialog
+            message(failure);
+            return null;
+        });
+}
+
 // 外部に公開
 export { showContextMenu };